@@ -0,0 +1,132 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::SolValue;
+use anyhow::{Context, Result};
+use apps::{bonsai::PollArgs, guest, poap::POAP_CONTRACT, ProverKind};
+use clap::Parser;
+use methods::{AGGREGATE_HOLDERS_ELF, IS_POAP_OWNER_ID};
+use risc0_zkvm::{serde::to_vec, sha::Digest, Receipt};
+use zkpoap_types::{AggregateData, NullifierMode, PoapContract};
+
+/// Arguments of the aggregator CLI.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// ID of the POAP event all aggregated proofs are for.
+    #[clap(long)]
+    event_id: U256,
+
+    /// Address of the `POAPGroup` all aggregated proofs are for.
+    #[clap(long)]
+    group_contract: Address,
+
+    /// Chain ID of the `POAPGroup`.
+    #[clap(long)]
+    chain_id: u64,
+
+    /// Address of the POAP contract all aggregated proofs queried.
+    #[clap(long, default_value_t = POAP_CONTRACT)]
+    poap_contract: Address,
+
+    /// Chain ID of the POAP contract.
+    #[clap(long, default_value_t = 100)]
+    poap_chain_id: u64,
+
+    /// Nullifier mode of the `POAPGroup`; proofs in another mode are rejected.
+    #[clap(long, value_enum, default_value_t = NullifierMode::Address)]
+    nullifier_mode: NullifierMode,
+
+    /// Backend generating the proof.
+    #[clap(long, value_enum, default_value_t = ProverKind::Bonsai)]
+    prover: ProverKind,
+
+    #[clap(flatten)]
+    poll: PollArgs,
+
+    /// Receipts of the `is_poap_owner` guest, as saved by `publisher --receipt`.
+    #[clap(required = true)]
+    receipts: Vec<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+
+    let receipts = args
+        .receipts
+        .iter()
+        .map(|path| {
+            let buf = std::fs::read(path)
+                .with_context(|| format!("failed to read receipt {}", path.display()))?;
+            let receipt: Receipt = bincode::deserialize(&buf)
+                .with_context(|| format!("invalid receipt {}", path.display()))?;
+            receipt
+                .verify(IS_POAP_OWNER_ID)
+                .with_context(|| format!("receipt {} does not verify", path.display()))?;
+            Ok(receipt)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // The guest verifies the journals against the assumptions, so only the journals are input.
+    let journals: Vec<Vec<u8>> = receipts
+        .iter()
+        .map(|receipt| receipt.journal.bytes.clone())
+        .collect();
+    let poap = PoapContract {
+        address: args.poap_contract,
+        chain_id: args.poap_chain_id,
+    };
+    let input = to_vec(&(
+        Digest::from(IS_POAP_OWNER_ID),
+        args.event_id,
+        args.group_contract,
+        args.chain_id,
+        poap,
+        args.nullifier_mode,
+        journals,
+    ))?;
+    let input: Vec<u8> = bytemuck::cast_slice(&input).to_vec();
+
+    // Run the guest locally first, so that duplicate holders or proofs for another group or
+    // POAP contract fail before proving.
+    guest::execute_with_assumptions(AGGREGATE_HOLDERS_ELF, &input, &receipts)?;
+
+    let (_receipt, (journal, post_state_digest, seal)) = args
+        .prover
+        .prover(args.poll.clone().into(), None)
+        .prove_with_assumptions(AGGREGATE_HOLDERS_ELF, &input, &receipts)?;
+
+    let aggregate =
+        AggregateData::abi_decode(&journal, true).context("invalid aggregate journal")?;
+    println!(
+        "Proved {} distinct holders of a POAP from event {}",
+        aggregate.count, aggregate.eventId
+    );
+    // The proofs are only sound if these blocks are part of the POAP chain.
+    for block in &aggregate.blocks {
+        println!(
+            "Proven at block {} with hash {}",
+            block.blockNumber, block.blockHash
+        );
+    }
+    println!("Journal: 0x{}", hex::encode(&journal));
+    println!("Post-state digest: {}", post_state_digest);
+    println!("Seal: 0x{}", hex::encode(&seal));
+
+    Ok(())
+}
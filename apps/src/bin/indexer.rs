@@ -0,0 +1,68 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use alloy_primitives::Address;
+use anyhow::Result;
+use apps::indexer::{self, GroupIndexer};
+use clap::Parser;
+use ethers::providers::{Http, Provider};
+use tokio::sync::RwLock;
+
+/// Arguments of the indexer CLI.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Ethereum Node endpoint.
+    #[arg(short, long, env = "RPC_URL")]
+    rpc_url: String,
+
+    /// Address of the `POAPGroup` to index.
+    #[clap(long)]
+    contract: Address,
+
+    /// Block the events are indexed from, e.g. the deployment block of the `POAPGroup`.
+    #[clap(long, default_value_t = 0)]
+    from_block: u64,
+
+    /// File the index is saved to and resumed from.
+    #[clap(long, default_value = "group-index.json")]
+    index_file: PathBuf,
+
+    /// Port the API is served on.
+    #[clap(long, default_value_t = 8081)]
+    port: u16,
+
+    /// Seconds between two syncs of the index.
+    #[clap(long, default_value_t = 12)]
+    poll_interval: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+
+    let provider = Provider::<Http>::try_from(args.rpc_url.as_str())?;
+    let mut indexer =
+        GroupIndexer::open(&provider, args.contract, args.from_block, args.index_file).await?;
+    indexer.sync(&provider).await?;
+
+    let indexer = Arc::new(RwLock::new(indexer));
+    let interval = Duration::from_secs(args.poll_interval);
+    tokio::spawn(indexer::follow(indexer.clone(), provider, interval));
+
+    indexer::serve(indexer, args.port).await
+}
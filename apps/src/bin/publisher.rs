@@ -12,47 +12,144 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloy_primitives::{address, Address, U256};
-use alloy_sol_types::{sol, SolCall, SolInterface};
-use anyhow::Result;
-use apps::{BonsaiProver, TxSender};
-use clap::Parser;
-use k256::ecdsa::{
-    signature::Signer,
-    Signature, SigningKey, VerifyingKey,
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
-use methods::IS_POAP_OWNER_ELF;
-use rand_core::OsRng;
-use risc0_ethereum_view_call::{
-    ethereum::EthViewCallEnv, EvmHeader, ViewCall,
-};
-use risc0_ethereum_view_call::config::GNOSIS_CHAIN_SPEC;
-use risc0_zkvm::serde::to_vec;
 
-sol! {
-    interface ISemaphore {
-        function joinGroup(bytes memory journal, bytes32 post_state_digest, bytes calldata seal);
-    }
-}
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_sol_types::SolCall;
+use anyhow::{bail, ensure, Context, Result};
+use apps::{
+    bonsai::{PollArgs, ProofState},
+    composition::{self, CompositionInputs},
+    group::{is_nullifier_used, SemaphoreGroup},
+    guest,
+    journal::{BatchJournal, Journal, SetJournal, ValidateBlock},
+    message::sign,
+    poap::{find_poap_index, find_poap_indices, POAP_CONTRACT},
+    relayer::RelayerClient,
+    semaphore::{create_secret_file, Identity},
+    signal::{self, SignalInputs},
+    signer::{decrypt_keystore, SignerArgs},
+    submission::{Guest, Submission},
+    tx::FeeArgs,
+    BonsaiProver, Groth16Unavailable, Prover, ProverKind, TxSender,
+};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use ethers::providers::{Http, Provider};
+use k256::ecdsa::SigningKey;
+use methods::{IS_POAP_OWNER_BATCH_ELF, IS_POAP_OWNER_ELF, IS_POAP_SET_MEMBER_ELF};
+use risc0_ethereum_view_call::{ethereum::EthViewCallEnv, EvmHeader, ViewCall};
+use risc0_zkvm::{serde::to_vec, Receipt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use zkpoap_types::{
+    eip712_domain, recover_owner, BatchEntry, GroupBinding, MessageFormat, NullifierMode,
+    PoapContract, SemaphoreIdentity, SetBinding, POAP,
+};
 
-sol! {
-    interface POAP {
-        function tokenDetailsOfOwnerByIndex(address owner, uint256 index) external view returns (uint256, uint256);
-    }
-}
+/// Maximum number of blocks between `--held-since-block` and the current block.
+///
+/// All headers in between are part of the guest input and hashed by the guest to link both
+/// blocks, so the input size and the proving cost grow with each block: 10,000 blocks are about
+/// 14 hours of Gnosis Chain and 33 hours of Ethereum. The group only accepts a historical block at
+/// most its `heldSinceBlock`, so groups with an older one can no longer be joined.
+const MAX_HELD_SINCE_BLOCKS: u64 = 10_000;
 
 /// Arguments of the publisher CLI.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
-struct Args {
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+/// Stages of the publisher. `run` performs all of them, the others read and write the files passed
+/// between stages, so that each one can run on a different machine.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build the guest input, prove it and submit the proof to the group.
+    Run {
+        #[clap(flatten)]
+        group: GroupArgs,
+        #[clap(flatten)]
+        submit: SubmitArgs,
+        #[clap(flatten)]
+        inputs: InputArgs,
+        #[clap(flatten)]
+        prove: ProveArgs,
+    },
+    /// Preflight the view calls and write the guest input to a file.
+    Preflight {
+        #[clap(flatten)]
+        group: GroupArgs,
+        #[clap(flatten)]
+        inputs: InputArgs,
+        /// File the guest input is written to.
+        #[clap(long, default_value = "input.json")]
+        out: PathBuf,
+    },
+    /// Execute the guest locally on an input file and print the decoded journal.
+    Execute {
+        /// Guest input written by `preflight`.
+        #[clap(long, default_value = "input.json")]
+        input: PathBuf,
+    },
+    /// Prove an input file and write the proof to a file.
+    Prove {
+        /// Guest input written by `preflight`.
+        #[clap(long, default_value = "input.json")]
+        input: PathBuf,
+        #[clap(flatten)]
+        prove: ProveArgs,
+        /// Resume the Bonsai proof request saved in the given state file instead of proving the
+        /// input file.
+        #[clap(long)]
+        resume: Option<PathBuf>,
+        /// File the proof is written to.
+        #[clap(long, default_value = "proof.json")]
+        out: PathBuf,
+    },
+    /// Check a proof file against the image ID of its guest.
+    Verify {
+        /// Proof written by `prove`.
+        #[clap(long, default_value = "proof.json")]
+        proof: PathBuf,
+        /// Receipt saved by `prove --receipt`, verified against the image ID and the journal.
+        #[clap(long)]
+        receipt: Option<PathBuf>,
+    },
+    /// Submit a proof file to the group.
+    Submit {
+        #[clap(flatten)]
+        group: GroupArgs,
+        #[clap(flatten)]
+        submit: SubmitArgs,
+        /// Proof written by `prove`.
+        #[clap(long, default_value = "proof.json")]
+        proof: PathBuf,
+    },
+    /// Send a Semaphore signal of a member to `POAPGroup.validateSignal`, or print its calldata
+    /// if no wallet is given.
+    Signal {
+        #[clap(flatten)]
+        group: GroupArgs,
+        #[clap(flatten)]
+        wallet: SignerArgs,
+        #[clap(flatten)]
+        fees: FeeArgs,
+        #[clap(flatten)]
+        signal: SignalArgs,
+    },
+}
+
+/// The group the proof is made for and submitted to.
+#[derive(Args, Debug)]
+struct GroupArgs {
     /// Ethereum chain ID
     #[clap(long)]
     chain_id: u64,
 
-    /// Ethereum Node endpoint.
-    #[clap(long, env)]
-    eth_wallet_private_key: String,
-
     /// Ethereum Node endpoint.
     #[arg(short, long, env = "RPC_URL")]
     rpc_url: String,
@@ -62,62 +159,844 @@ struct Args {
     contract: String,
 }
 
+/// How the proof is submitted: from a wallet, or through a relayer paying for the transaction.
+#[derive(Args, Debug)]
+struct SubmitArgs {
+    #[clap(flatten)]
+    wallet: SignerArgs,
+
+    #[clap(flatten)]
+    fees: FeeArgs,
+
+    /// Base URL of a relayer submitting the proof from its own wallet, so that no wallet of the
+    /// holder is used.
+    #[clap(long, conflicts_with = "signer")]
+    relayer: Option<String>,
+}
+
+impl SubmitArgs {
+    /// Checks that the proof can be submitted, before spending time on proving it.
+    fn check(&self) -> Result<()> {
+        ensure!(
+            self.relayer.is_some() || self.wallet.is_selected(),
+            "no wallet selected; pass --keystore, --aws-kms-key-id, --remote-signer or --relayer"
+        );
+        Ok(())
+    }
+}
+
+/// Arguments of a Semaphore signal.
+#[derive(Args, Debug)]
+struct SignalArgs {
+    /// Semaphore identity file of the member.
+    #[clap(long, default_value = "semaphore-identity.json")]
+    identity: PathBuf,
+
+    /// Scope of the signal, e.g. the ID of a poll. A member has a single nullifier hash per scope.
+    #[clap(long)]
+    scope: U256,
+
+    /// Signal to send, e.g. a vote.
+    #[clap(long)]
+    message: U256,
+
+    /// Block the `MemberAdded` events of the group are searched from, e.g. the deployment block
+    /// of `POAPGroup`.
+    #[clap(long, default_value_t = 0)]
+    from_block: u64,
+
+    /// Groth16 proof of the Semaphore circuit, as written by `snarkjs groth16 fullprove`.
+    #[clap(long)]
+    semaphore_proof: Option<PathBuf>,
+
+    /// Semaphore circuit of depth 20, to generate the proof with snarkjs.
+    #[clap(long, requires = "zkey")]
+    wasm: Option<PathBuf>,
+
+    /// Proving key of the Semaphore circuit of depth 20, to generate the proof with snarkjs.
+    #[clap(long, requires = "wasm")]
+    zkey: Option<PathBuf>,
+
+    /// File the inputs of the Semaphore circuit are written to when there is no proof or circuit.
+    /// They contain the secrets of the identity, so the file is only readable by the user.
+    #[clap(long, default_value = "signal-input.json")]
+    out: PathBuf,
+}
+
+/// Arguments the guest input is built from.
+///
+/// The signature of the POAP owner is given with `--signature`, or made with the key of
+/// `--poap-owner-keystore` or, for development keys, `--insecure-poap-owner-private-key`; none is
+/// read from the environment.
+#[derive(Args, Debug)]
+#[clap(group(
+    ArgGroup::new("poap_owner").args([
+        "signature",
+        "poap_owner_keystore",
+        "insecure_poap_owner_private_key",
+    ])
+))]
+struct InputArgs {
+    /// Address of the POAP contract.
+    #[clap(long, default_value_t = POAP_CONTRACT)]
+    poap_contract: Address,
+
+    /// Chain ID of the POAP contract.
+    #[clap(long, default_value_t = 100)]
+    poap_chain_id: u64,
+
+    /// Node endpoint of the POAP chain. Defaults to the Ethereum Node endpoint.
+    #[clap(long, env)]
+    poap_rpc_url: Option<String>,
+
+    /// ID of the POAP event.
+    #[clap(long, required_unless_present = "event_set")]
+    event_id: Option<U256>,
+
+    /// Comma separated event IDs of the set of a `POAPSetGroup`, to prove POAPs from at least
+    /// `--threshold` of them with the `is_poap_set_member` guest instead of a POAP from
+    /// `--event-id`.
+    #[clap(
+        long,
+        value_delimiter = ',',
+        conflicts_with_all = ["event_id", "batch", "compose", "held_since_block"]
+    )]
+    event_set: Option<Vec<U256>>,
+
+    /// Number of distinct events of `--event-set` the holder must have a POAP from; must match
+    /// the threshold of the `POAPSetGroup` deployment.
+    #[clap(long, requires = "event_set", default_value_t = 1)]
+    threshold: u64,
+
+    /// Block of the POAP chain since which the POAP must have been held, for groups with a minimum
+    /// holding age. All headers from this block to the current one are part of the input, so it
+    /// must be before the current block, by at most 10,000 blocks.
+    #[clap(long)]
+    held_since_block: Option<u64>,
+
+    /// Semaphore identity file. A new identity is generated and saved if the file does not exist.
+    #[clap(long, default_value = "semaphore-identity.json")]
+    identity: PathBuf,
+
+    /// Precomputed Semaphore identity commitment to add to the group instead of the identity.
+    #[clap(long)]
+    semaphore_commitment: Option<U256>,
+
+    /// Pass the secrets of the identity to the guest, which computes the commitment, instead of
+    /// the commitment computed here. The guest input is uploaded to the prover and written by
+    /// `preflight`, so anyone reading it can send signals as the member.
+    #[clap(long, conflicts_with = "semaphore_commitment")]
+    identity_secret_in_guest: bool,
+
+    /// What the nullifier is keyed on; must match the mode of the `POAPGroup` deployment.
+    #[clap(long, value_enum, default_value_t = NullifierMode::Address)]
+    nullifier_mode: NullifierMode,
+
+    /// Format of the message signed by the POAP holder.
+    #[clap(long, value_enum, default_value_t = Format::Eip712)]
+    message_format: Format,
+
+    /// Unix timestamp at which an EIP-712 signature expires. Defaults to one hour from now.
+    #[clap(long)]
+    deadline: Option<u64>,
+
+    /// Hex encoded 65-byte signature of the message, e.g. produced by a wallet.
+    #[clap(long)]
+    signature: Option<String>,
+
+    /// Encrypted JSON keystore of the wallet holding the POAP, signing the message when no
+    /// signature is given.
+    #[clap(long)]
+    poap_owner_keystore: Option<PathBuf>,
+
+    /// File containing the password of the keystore of the POAP owner. The password is prompted
+    /// for if not given.
+    #[clap(long, requires = "poap_owner_keystore")]
+    poap_owner_password_file: Option<PathBuf>,
+
+    /// Hex encoded private key of the wallet holding the POAP. Only meant for development keys:
+    /// the key is visible to other users in `ps` and kept in the shell history.
+    #[clap(long)]
+    insecure_poap_owner_private_key: Option<String>,
+
+    /// JSON file of members to add with a single proof, instead of the single holder. Each entry
+    /// has a `signature`, a `semaphore_commitment`, and optionally an `owner` and a `deadline`.
+    #[clap(long)]
+    batch: Option<PathBuf>,
+
+    /// Prove ownership and the signature with separate guests, composed into a single proof.
+    #[clap(long)]
+    compose: bool,
+}
+
+/// Arguments of the prover.
+#[derive(Args, Debug)]
+struct ProveArgs {
+    /// Backend generating the proof.
+    #[clap(long, value_enum, default_value_t = ProverKind::Bonsai)]
+    prover: ProverKind,
+
+    #[clap(flatten)]
+    poll: PollArgs,
+
+    /// File to save the receipt of the proof to, so that it can be aggregated by `aggregator`.
+    #[clap(long)]
+    receipt: Option<PathBuf>,
+
+    /// File the progress of the Bonsai proof request is saved to. A new request fails if it tracks
+    /// an unfinished one.
+    #[clap(long, default_value = "proof-state.json")]
+    state_file: PathBuf,
+}
+
+impl ProveArgs {
+    fn prover(&self) -> Box<dyn Prover> {
+        self.prover
+            .prover(self.poll.clone().into(), Some(self.state_file.clone()))
+    }
+}
+
+/// A member of the `--batch` file.
+#[derive(Deserialize, Debug)]
+struct BatchMember {
+    /// Hex encoded 65-byte signature of the message.
+    signature: String,
+    /// Semaphore identity commitment of the member.
+    semaphore_commitment: U256,
+    /// Expected signer of the message.
+    owner: Option<Address>,
+    /// Deadline of an EIP-712 signature; EIP-191 is assumed without a deadline.
+    deadline: Option<u64>,
+}
+
+/// Message formats accepted by the guest.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    /// `personal_sign` message as specified by EIP-191.
+    Eip191,
+    /// `eth_signTypedData_v4` message as specified by EIP-712.
+    Eip712,
+}
+
+/// The guest input written by `preflight`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "guest", rename_all = "kebab-case")]
+enum GuestInput {
+    IsPoapOwner { input: Bytes },
+    IsPoapOwnerBatch { input: Bytes },
+    ComposePoapOwner { ownership: Bytes, signature: Bytes },
+    IsPoapSetMember { input: Bytes },
+}
+
+impl GuestInput {
+    /// Runs the guest locally, so that rejected inputs fail before requesting a proof, and
+    /// returns the decoded journal.
+    fn execute(&self) -> Result<String> {
+        match self {
+            GuestInput::IsPoapOwner { input } => {
+                let session = guest::execute(IS_POAP_OWNER_ELF, input)?;
+                Ok(format!("{:#?}", Journal::decode(&session.journal.bytes)?))
+            }
+            GuestInput::IsPoapOwnerBatch { input } => {
+                let session = guest::execute(IS_POAP_OWNER_BATCH_ELF, input)?;
+                Ok(format!(
+                    "{:#?}",
+                    BatchJournal::decode(&session.journal.bytes)?
+                ))
+            }
+            GuestInput::IsPoapSetMember { input } => {
+                let session = guest::execute(IS_POAP_SET_MEMBER_ELF, input)?;
+                let journal = SetJournal::decode(&session.journal.bytes)?;
+                // `POAPSetGroup` rejects proofs of holders below the threshold.
+                ensure!(
+                    journal.proof_data.thresholdMet,
+                    "the threshold of the set is not met"
+                );
+                Ok(format!("{:#?}", journal))
+            }
+            // The composed journal only exists once both parts are proven.
+            GuestInput::ComposePoapOwner { .. } => {
+                self.composition_inputs().execute()?;
+                Ok("both composed guests accepted their inputs".to_string())
+            }
+        }
+    }
+
+    /// Proves the input with the given prover, saving the receipt to `receipt` if given.
+    fn prove(&self, prover: &dyn Prover, receipt: Option<&Path>) -> Result<Submission> {
+        let (guest, elf, input) = match self {
+            GuestInput::IsPoapOwner { input } => (Guest::IsPoapOwner, IS_POAP_OWNER_ELF, input),
+            GuestInput::IsPoapOwnerBatch { input } => {
+                (Guest::IsPoapOwnerBatch, IS_POAP_OWNER_BATCH_ELF, input)
+            }
+            GuestInput::IsPoapSetMember { input } => {
+                (Guest::IsPoapSetMember, IS_POAP_SET_MEMBER_ELF, input)
+            }
+            GuestInput::ComposePoapOwner { .. } => {
+                ensure!(
+                    receipt.is_none(),
+                    "--receipt is not supported for composed proofs"
+                );
+                let snark = composition::prove(prover, &self.composition_inputs())?;
+                return Ok(Submission::new(Guest::ComposePoapOwner, snark));
+            }
+        };
+
+        let (proof_receipt, snark) = match prover.prove_with_assumptions(elf, input, &[]) {
+            Ok(proof) => proof,
+            // Without a seal, the receipt can still be saved, e.g. to be aggregated.
+            Err(err) => {
+                if let (true, Some(path)) = (err.is::<Groth16Unavailable>(), receipt) {
+                    save_receipt(path, &prover.prove_receipt(elf, input, &[])?)?;
+                }
+                return Err(err);
+            }
+        };
+        if let Some(path) = receipt {
+            save_receipt(path, &proof_receipt)?;
+        }
+        Ok(Submission::new(guest, snark))
+    }
+
+    fn composition_inputs(&self) -> CompositionInputs {
+        match self {
+            GuestInput::ComposePoapOwner {
+                ownership,
+                signature,
+            } => CompositionInputs {
+                ownership: ownership.to_vec(),
+                signature: signature.to_vec(),
+            },
+            _ => unreachable!("not a composed input"),
+        }
+    }
+}
+
 fn main() -> Result<()> {
-    let args = Args::parse();
+    env_logger::init();
 
-    let tx_sender = TxSender::new(
-        args.chain_id,
-        &args.rpc_url,
-        &args.eth_wallet_private_key,
-        &args.contract,
-    )?;
+    match Cli::parse().command {
+        Command::Run {
+            group,
+            submit: submit_args,
+            inputs,
+            prove,
+        } => {
+            submit_args.check()?;
+            let input = preflight(&group, &inputs)?;
+            println!("Running the guest with the constructed input:");
+            println!("{}", input.execute()?);
+
+            // Generate the proof with the selected prover, by default on the Bonsai proving
+            // service.
+            let proof = input.prove(prove.prover().as_ref(), prove.receipt.as_deref())?;
+            submit(&group, &submit_args, &proof)
+        }
+        Command::Preflight { group, inputs, out } => {
+            let input = preflight(&group, &inputs)?;
+            write_json(&out, &input)?;
+            println!("Saved guest input to {}", out.display());
+            Ok(())
+        }
+        Command::Execute { input } => {
+            let input: GuestInput = read_json(&input)?;
+            println!("{}", input.execute()?);
+            Ok(())
+        }
+        Command::Prove {
+            input,
+            prove,
+            resume,
+            out,
+        } => {
+            let proof = match resume {
+                // Continue a proof request of a previous run from its last completed stage.
+                Some(state_file) => {
+                    ensure!(
+                        prove.prover == ProverKind::Bonsai,
+                        "only Bonsai proof requests can be resumed"
+                    );
+                    let state = ProofState::load(&state_file)?;
+                    let guest = Guest::from_image_id(&state.image_id)?;
+                    let bonsai = BonsaiProver {
+                        poll: prove.poll.clone().into(),
+                        state_file: Some(state_file),
+                    };
+                    let (receipt, snark) = bonsai.resume(state)?;
+                    if let Some(path) = &prove.receipt {
+                        save_receipt(path, &receipt)?;
+                    }
+                    Submission::new(guest, snark)
+                }
+                None => {
+                    let input: GuestInput = read_json(&input)?;
+                    input.execute()?;
+                    input.prove(prove.prover().as_ref(), prove.receipt.as_deref())?
+                }
+            };
+            write_json(&out, &proof)?;
+            println!("Saved proof to {}", out.display());
+            Ok(())
+        }
+        Command::Verify { proof, receipt } => {
+            let proof: Submission = read_json(&proof)?;
+            verify(&proof, receipt.as_deref())?;
+            println!("Proof of {:?} is valid", proof.guest()?);
+            Ok(())
+        }
+        Command::Submit {
+            group,
+            submit: submit_args,
+            proof,
+        } => {
+            submit_args.check()?;
+            let proof: Submission = read_json(&proof)?;
+            submit(&group, &submit_args, &proof)
+        }
+        Command::Signal {
+            group,
+            wallet,
+            fees,
+            signal,
+        } => send_signal(&group, &wallet, fees, &signal),
+    }
+}
+
+/// Builds the input of the guest selected by the arguments, preflighting its view calls.
+fn preflight(group: &GroupArgs, args: &InputArgs) -> Result<GuestInput> {
+    let poap = PoapContract {
+        address: args.poap_contract,
+        chain_id: args.poap_chain_id,
+    };
+    let poap_rpc_url = args.poap_rpc_url.as_deref().unwrap_or(&group.rpc_url);
+
+    // Add all members of the batch with a single proof, so that the verifier cost is paid once.
+    if let Some(batch) = &args.batch {
+        let input = get_batch_inputs(poap_rpc_url, &poap, group, args, batch)?;
+        return Ok(GuestInput::IsPoapOwnerBatch {
+            input: input.into(),
+        });
+    }
 
-    let signing_key = SigningKey::random(&mut OsRng); 
-    let message = b"This is a message that will be signed, and verified within the zkVM";
-    let signature: Signature = signing_key.sign(message);
-    let poap_index: U256 = U256::from(0);
+    let semaphore_identity = match args.semaphore_commitment {
+        Some(commitment) => SemaphoreIdentity::Commitment(commitment),
+        None if args.identity_secret_in_guest => {
+            log::warn!("The secrets of the Semaphore identity are part of the guest input");
+            Identity::load_or_create(&args.identity)?.to_secret_input()
+        }
+        None => Identity::load_or_create(&args.identity)?.into(),
+    };
+    println!(
+        "Semaphore identity commitment: {}",
+        semaphore_identity.commitment()
+    );
+    let format = match args.message_format {
+        Format::Eip191 => MessageFormat::Eip191,
+        Format::Eip712 => {
+            let deadline = match args.deadline {
+                Some(deadline) => deadline,
+                None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + 3600,
+            };
+            MessageFormat::Eip712 {
+                deadline: U256::from(deadline),
+            }
+        }
+    };
 
-    let input =
-        get_verification_inputs(signing_key.verifying_key(), message, &signature, poap_index)
-            .unwrap();
+    // Prove POAPs from several events of a set, for a `POAPSetGroup`.
+    if let Some(event_set) = &args.event_set {
+        // The guest requires the event IDs sorted in strictly ascending order.
+        let mut event_ids = event_set.clone();
+        event_ids.sort();
+        event_ids.dedup();
+        let binding = SetBinding {
+            event_ids,
+            threshold: args.threshold,
+            group_contract: group.contract.parse()?,
+            chain_id: group.chain_id,
+            semaphore_identity,
+        };
+        let signature = get_signature(
+            args,
+            |signing_key| sign(binding.signing_hash(&format), signing_key),
+            || match &format {
+                MessageFormat::Eip191 => format!("Message to sign: {}", binding.eip191_message()),
+                MessageFormat::Eip712 { deadline } => format!(
+                    "Typed data to sign: {:?} in domain {:?}",
+                    binding.eip712_message(*deadline),
+                    eip712_domain(binding.chain_id, binding.group_contract)
+                ),
+            },
+        )?;
+        let input = get_set_membership_inputs(poap_rpc_url, &poap, &binding, &format, &signature)?;
+        return Ok(GuestInput::IsPoapSetMember {
+            input: input.into(),
+        });
+    }
 
-    // Send an off-chain proof request to the Bonsai proving service.
-    let (journal, post_state_digest, seal) = BonsaiProver::prove(IS_POAP_OWNER_ELF, &input)?;
+    // The message is bound to the group the proof is submitted to.
+    let binding = GroupBinding {
+        event_id: args.event_id.context("--event-id is required")?,
+        group_contract: group.contract.parse()?,
+        chain_id: group.chain_id,
+        semaphore_identity,
+        nullifier_mode: args.nullifier_mode,
+    };
+    let signature = get_signature(
+        args,
+        |signing_key| sign(binding.signing_hash(&format), signing_key),
+        || match &format {
+            MessageFormat::Eip191 => format!("Message to sign: {}", binding.eip191_message()),
+            MessageFormat::Eip712 { deadline } => format!(
+                "Typed data to sign: {:?} in domain {:?}",
+                binding.eip712_message(*deadline),
+                eip712_domain(binding.chain_id, binding.group_contract)
+            ),
+        },
+    )?;
 
-    let calldata = ISemaphore::ISemaphoreCalls::joinGroup(ISemaphore::joinGroupCall {
-        journal: journal.into(),
-        post_state_digest,
-        seal: seal.into(),
+    // Prove ownership and the signature separately, and compose both receipts.
+    if args.compose {
+        ensure!(
+            args.held_since_block.is_none(),
+            "--held-since-block is not supported with --compose"
+        );
+        let inputs = get_composition_inputs(poap_rpc_url, &poap, &binding, &format, &signature)?;
+        return Ok(GuestInput::ComposePoapOwner {
+            ownership: inputs.ownership.into(),
+            signature: inputs.signature.into(),
+        });
+    }
+
+    let input = get_verification_inputs(
+        poap_rpc_url,
+        &poap,
+        &binding,
+        &format,
+        &signature,
+        args.held_since_block,
+    )?;
+    Ok(GuestInput::IsPoapOwner {
+        input: input.into(),
     })
-    .abi_encode();
+}
+
+/// Returns the signature of the message given with `--signature`, or signs it with the key of
+/// `--poap-owner-keystore` or `--insecure-poap-owner-private-key`. Without any, prints the message
+/// to sign and fails.
+fn get_signature(
+    args: &InputArgs,
+    sign: impl FnOnce(&SigningKey) -> Result<Vec<u8>>,
+    message: impl FnOnce() -> String,
+) -> Result<Vec<u8>> {
+    if let Some(signature) = &args.signature {
+        return Ok(hex::decode(signature.trim_start_matches("0x"))?);
+    }
+
+    let signing_key = if let Some(keystore) = &args.poap_owner_keystore {
+        let wallet = decrypt_keystore(keystore, args.poap_owner_password_file.as_deref())?;
+        SigningKey::from_slice(&wallet.signer().to_bytes())?
+    } else if let Some(private_key) = &args.insecure_poap_owner_private_key {
+        eprintln!(
+            "Warning: signing with the raw key of --insecure-poap-owner-private-key, which is only \
+             meant for development keys; use --signature or --poap-owner-keystore instead"
+        );
+        SigningKey::from_slice(&hex::decode(private_key.trim_start_matches("0x"))?)?
+    } else {
+        println!("{}", message());
+        bail!("sign the message and pass --signature, or pass --poap-owner-keystore")
+    };
+    sign(&signing_key)
+}
+
+/// Checks that the proof is of a guest accepted by `POAPGroup`, that its journal decodes and that
+/// its Groth16 seal verifies, and verifies the receipt, if given, against the image ID and the
+/// journal of the proof.
+fn verify(proof: &Submission, receipt: Option<&Path>) -> Result<()> {
+    proof.verify()?;
+
+    if let Some(path) = receipt {
+        let guest = proof.guest()?;
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("failed to read receipt {}", path.display()))?;
+        let receipt: Receipt = bincode::deserialize(&bytes).context("invalid receipt")?;
+        receipt
+            .verify(guest.image_id())
+            .context("receipt does not verify against the image ID")?;
+        ensure!(
+            receipt.journal.bytes == &proof.journal[..],
+            "receipt is for another journal than the proof"
+        );
+    }
+    Ok(())
+}
+
+/// Writes the receipt to the given file, as read by `aggregator`.
+fn save_receipt(path: &Path, receipt: &Receipt) -> Result<()> {
+    std::fs::write(path, bincode::serialize(receipt)?)
+        .with_context(|| format!("failed to write receipt to {}", path.display()))?;
+    println!("Saved receipt to {}", path.display());
+    Ok(())
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("invalid file {}", path.display()))
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Writes a file only readable by the user, for values containing secrets. An existing file is
+/// replaced, so that it does not keep the permissions it was created with.
+fn write_secret_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let file = create_secret_file(path)?;
+    serde_json::to_writer_pretty(file, value)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Sends the proof to Ethereum, from the wallet or through the relayer, after verifying it
+/// locally, warning first if the proven block is too old to be accepted.
+fn submit(group: &GroupArgs, args: &SubmitArgs, proof: &Submission) -> Result<()> {
+    // An invalid proof would only be rejected by the verifier, after paying for the transaction.
+    verify(proof, None)?;
+
+    let (block, calldata) = proof.calldata()?;
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    // Proving takes a while, so the proven block may be too old by now.
+    let provider = Provider::<Http>::try_from(group.rpc_url.as_str())?;
+    // The POAP chain is committed by the guest; blocks of another chain are checked against the
+    // block hash oracle of the group, as `validateBlock` does.
+    let validation = block.validate(&provider, group.contract.parse()?, proof.poap_chain_id()?);
+    if let Err(err) = runtime.block_on(validation) {
+        println!("Warning: the proof will likely be rejected: {:#}", err);
+    }
+
+    // The relayer sends the transaction from its own wallet.
+    if let Some(url) = &args.relayer {
+        let tx_hash = runtime.block_on(RelayerClient::new(url).submit(proof))?;
+        println!("The relayer sent transaction {} to join the group", tx_hash);
+        return Ok(());
+    }
+
+    let signer = runtime.block_on(args.wallet.signer(group.chain_id))?;
+    let tx_sender = TxSender::with_signer(group.chain_id, &group.rpc_url, signer, &group.contract)?
+        .with_fees(args.fees.clone().try_into()?);
 
     // Send the calldata to Ethereum.
+    let receipt = runtime.block_on(tx_sender.send(calldata))?;
+    println!(
+        "Joined the group in transaction {:?}",
+        receipt.transaction_hash
+    );
+
+    Ok(())
+}
+
+/// Builds the Semaphore signal of the member from the group rebuilt at the latest block, and sends
+/// it from the wallet, or prints its calldata.
+fn send_signal(
+    group: &GroupArgs,
+    wallet: &SignerArgs,
+    fees: FeeArgs,
+    args: &SignalArgs,
+) -> Result<()> {
+    let identity = Identity::load(&args.identity)?;
     let runtime = tokio::runtime::Runtime::new()?;
-    runtime.block_on(tx_sender.send(calldata))?;
+    let provider = Provider::<Http>::try_from(group.rpc_url.as_str())?;
+
+    let semaphore_group =
+        runtime.block_on(SemaphoreGroup::of(&provider, group.contract.parse()?))?;
+    let members = runtime.block_on(semaphore_group.members(&provider, args.from_block))?;
+    let tree = semaphore_group.tree(&members)?;
+    let inputs = SignalInputs::new(&identity, &tree, args.scope, args.message)?;
+    println!("Nullifier hash of the scope: {}", inputs.nullifier_hash);
+
+    let artifacts = args.wasm.as_deref().zip(args.zkey.as_deref());
+    if args.semaphore_proof.is_none() && artifacts.is_none() {
+        write_secret_json(&args.out, &inputs.circuit)?;
+        println!(
+            "Saved the inputs of the Semaphore circuit to {}; prove them with `snarkjs groth16 \
+             fullprove` and pass the proof with --semaphore-proof",
+            args.out.display()
+        );
+        println!(
+            "Warning: {} contains the secrets of the Semaphore identity, with which anyone can \
+             send signals as the member; delete it once proven",
+            args.out.display()
+        );
+        return Ok(());
+    }
+    let proof = signal::signal_proof(&inputs, args.semaphore_proof.as_deref(), artifacts)?;
+    let calldata = inputs.calldata(proof);
+
+    // The signal does not depend on the sender, so it can be sent by any account.
+    if !wallet.is_selected() {
+        println!("Calldata of validateSignal: 0x{}", hex::encode(&calldata));
+        return Ok(());
+    }
+    let signer = runtime.block_on(wallet.signer(group.chain_id))?;
+    let tx_sender = TxSender::with_signer(group.chain_id, &group.rpc_url, signer, &group.contract)?
+        .with_fees(fees.try_into()?);
+    let receipt = runtime.block_on(tx_sender.send(calldata))?;
+    println!(
+        "Sent the signal in transaction {:?}",
+        receipt.transaction_hash
+    );
 
     Ok(())
 }
 
+fn get_batch_inputs(
+    rpc_url: &str,
+    poap: &PoapContract,
+    group: &GroupArgs,
+    args: &InputArgs,
+    batch: &Path,
+) -> Result<Vec<u8>> {
+    let event_id = args.event_id.context("--event-id is required")?;
+    let json = std::fs::read_to_string(batch)
+        .with_context(|| format!("failed to read batch file {}", batch.display()))?;
+    let members: Vec<BatchMember> = serde_json::from_str(&json).context("invalid batch file")?;
+
+    // `joinGroupBatch` skips used nullifiers, so do not prove the members that already joined.
+    let runtime = tokio::runtime::Runtime::new()?;
+    let provider = Provider::<Http>::try_from(group.rpc_url.as_str())?;
+    let group_contract: Address = group.contract.parse()?;
+    let mut nullifiers = Vec::with_capacity(members.len());
+
+    // All view calls are preflighted against the same environment, so one input covers the batch.
+    let mut env = EthViewCallEnv::from_rpc(rpc_url, None)?.with_chain_spec(poap.chain_spec()?);
+    let number = env.header().number();
+
+    let mut entries = Vec::with_capacity(members.len());
+    for member in members {
+        let binding = GroupBinding {
+            event_id,
+            group_contract,
+            chain_id: group.chain_id,
+            semaphore_identity: SemaphoreIdentity::Commitment(member.semaphore_commitment),
+            nullifier_mode: args.nullifier_mode,
+        };
+        let format = match member.deadline {
+            Some(deadline) => MessageFormat::Eip712 {
+                deadline: U256::from(deadline),
+            },
+            None => MessageFormat::Eip191,
+        };
+        let signature = hex::decode(member.signature.trim_start_matches("0x"))?;
+
+        let owner = recover_owner(&signature, &binding.signing_hash(&format))?;
+        if let Some(expected) = member.owner {
+            ensure!(
+                owner == expected,
+                "signature for {} was made by {}",
+                expected,
+                owner
+            );
+        }
+
+        let poap_index = find_poap_index(rpc_url, number, poap, owner, event_id)?;
+        let call = POAP::tokenDetailsOfOwnerByIndexCall {
+            owner,
+            index: poap_index,
+        };
+        let returns = ViewCall::new(call, poap.address)
+            .with_caller(owner)
+            .preflight_call(&mut env)?;
+
+        let nullifier = binding.nullifier(owner, returns._0);
+        if nullifiers.contains(&nullifier)
+            || runtime.block_on(is_nullifier_used(&provider, group_contract, nullifier))?
+        {
+            println!("Skipping {}, who already joined the group", owner);
+            continue;
+        }
+        nullifiers.push(nullifier);
+
+        entries.push(BatchEntry {
+            binding,
+            format,
+            signature,
+            owner,
+            poap_index,
+        });
+    }
+    ensure!(
+        !entries.is_empty(),
+        "all members of the batch already joined the group"
+    );
+    println!("Proving {} members at block {}", entries.len(), number);
+
+    let view_call_input = env.into_input()?;
+    let input = InputBuilder::new()
+        .write(&view_call_input)?
+        .write(&(poap, entries))?
+        .bytes();
+
+    Ok(input)
+}
+
 fn get_verification_inputs(
-    verifying_key: &VerifyingKey,
-    message: &[u8],
-    signature: &Signature,
-    poap_index: U256,
+    rpc_url: &str,
+    poap: &PoapContract,
+    binding: &GroupBinding,
+    format: &MessageFormat,
+    signature: &[u8],
+    held_since_block: Option<u64>,
 ) -> Result<Vec<u8>> {
-    const CONTRACT: Address = address!("22C1f6050E56d2876009903609a2cC3fEf83B415");
-    const CALLER: Address = address!("6f22b9f222D9e9AF4481df55B863A567dfe1dd42");
+    // The guest recovers the POAP owner from the signature, so query the same address.
+    let owner = recover_owner(signature, &binding.signing_hash(format))?;
+
+    let mut env = EthViewCallEnv::from_rpc(rpc_url, None)?.with_chain_spec(poap.chain_spec()?);
+    let number = env.header().number();
+    if let Some(held_since_block) = held_since_block {
+        check_held_since_block(held_since_block, number)?;
+    }
+
+    // Preflight the same POAP at the historical block, and link the historical block to the
+    // current one through the ancestors of the input.
+    let held_since = match held_since_block {
+        Some(held_since_block) => {
+            let historical_index =
+                find_poap_index(rpc_url, held_since_block, poap, owner, binding.event_id)?;
+            let historical_env = EthViewCallEnv::from_rpc(rpc_url, Some(held_since_block))?
+                .with_chain_spec(poap.chain_spec()?);
+            let call = POAP::tokenDetailsOfOwnerByIndexCall {
+                owner,
+                index: historical_index,
+            };
+            let (historical_input, historical_returns) = ViewCall::new(call, poap.address)
+                .with_caller(owner)
+                .preflight(historical_env)?;
+            println!(
+                "For block {} POAP {} is held at index {}",
+                held_since_block, historical_returns._0, historical_index
+            );
+
+            log::info!(
+                "Including {} ancestor headers in the input",
+                number.saturating_sub(held_since_block)
+            );
+            env.preflight_block_hash(held_since_block)?;
+            Some((historical_input, historical_index, historical_returns._0))
+        }
+        None => None,
+    };
 
+    // Look up which POAP of the owner is from the event, at the same block as the proof.
+    let poap_index = find_poap_index(rpc_url, number, poap, owner, binding.event_id)?;
     let call: POAP::tokenDetailsOfOwnerByIndexCall = POAP::tokenDetailsOfOwnerByIndexCall {
-        owner: address!("6f22b9f222D9e9AF4481df55B863A567dfe1dd42"),
+        owner,
         index: poap_index,
     };
 
-    let env = EthViewCallEnv::from_rpc(&std::env::var("RPC_URL").unwrap(), None)?
-        .with_chain_spec(&GNOSIS_CHAIN_SPEC);
-    let number = env.header().number();
-
-    let (view_call_input, returns) = ViewCall::new(call, CONTRACT)
-        .with_caller(CALLER)
+    let (view_call_input, returns) = ViewCall::new(call, poap.address)
+        .with_caller(owner)
         .preflight(env)?;
     println!(
         "For block {} `{}` returns: token_id: {} - event_id: {}",
@@ -126,26 +1005,157 @@ fn get_verification_inputs(
         returns._0,
         returns._1
     );
-
-    let sig_data_inputs = (
-        verifying_key.to_encoded_point(true),
-        message,
-        signature,
+    ensure!(
+        returns._1 == binding.event_id,
+        "POAP at index {} is from event {}, not from event {}",
         poap_index,
+        returns._1,
+        binding.event_id
     );
+    println!("Nullifier: {}", binding.nullifier(owner, returns._0));
+
+    let held_since = match held_since {
+        Some((historical_input, historical_index, historical_token_id)) => {
+            ensure!(
+                historical_token_id == returns._0,
+                "POAP {} was not held at the historical block",
+                returns._0
+            );
+            Some((historical_input, historical_index))
+        }
+        None => None,
+    };
 
-    println!("Running the guest with the constructed input:");
+    let sig_data_inputs = (poap, binding, format, signature, poap_index);
 
     let input = InputBuilder::new()
-        .write(&view_call_input)
-        .unwrap()
-        .write(&sig_data_inputs)
-        .unwrap()
+        .write(&view_call_input)?
+        .write(&sig_data_inputs)?
+        .write(&held_since)?
         .bytes();
 
     Ok(input)
 }
 
+/// Returns an error unless the historical block is before the current block, by at most
+/// [MAX_HELD_SINCE_BLOCKS] blocks, before any header is fetched for the input.
+fn check_held_since_block(held_since_block: u64, current_block: u64) -> Result<()> {
+    // The guest requires the historical block to be an ancestor of the current block, which the
+    // current block is not of itself. Without --held-since-block, the current block is committed.
+    ensure!(
+        held_since_block < current_block,
+        "--held-since-block {} is not before the current block {}; omit it to prove ownership at \
+         the current block",
+        held_since_block,
+        current_block
+    );
+    let blocks = current_block - held_since_block;
+    ensure!(
+        blocks <= MAX_HELD_SINCE_BLOCKS,
+        "--held-since-block {} is {} blocks before the current block {}, more than the maximum of \
+         {} headers in the input",
+        held_since_block,
+        blocks,
+        current_block,
+        MAX_HELD_SINCE_BLOCKS
+    );
+    Ok(())
+}
+
+/// Builds the input of the `is_poap_set_member` guest, with one POAP per event of the set held by
+/// the signer.
+fn get_set_membership_inputs(
+    rpc_url: &str,
+    poap: &PoapContract,
+    binding: &SetBinding,
+    format: &MessageFormat,
+    signature: &[u8],
+) -> Result<Vec<u8>> {
+    // The guest recovers the POAP owner from the signature, so query the same address.
+    let owner = recover_owner(signature, &binding.signing_hash(format))?;
+
+    // All view calls are preflighted against the same environment, so one input covers the set.
+    let mut env = EthViewCallEnv::from_rpc(rpc_url, None)?.with_chain_spec(poap.chain_spec()?);
+    let number = env.header().number();
+
+    let poap_indices = find_poap_indices(rpc_url, number, poap, owner, &binding.event_ids)?;
+    ensure!(
+        poap_indices.len() as u64 >= binding.threshold,
+        "{} holds POAPs from {} events of the set at block {}, fewer than the threshold of {}",
+        owner,
+        poap_indices.len(),
+        number,
+        binding.threshold
+    );
+    for index in &poap_indices {
+        let call = POAP::tokenDetailsOfOwnerByIndexCall {
+            owner,
+            index: *index,
+        };
+        ViewCall::new(call, poap.address)
+            .with_caller(owner)
+            .preflight_call(&mut env)?;
+    }
+    println!(
+        "Proving POAPs from {} events of the set at block {}",
+        poap_indices.len(),
+        number
+    );
+    println!("Nullifier: {}", binding.nullifier(owner));
+
+    let view_call_input = env.into_input()?;
+    let input = InputBuilder::new()
+        .write(&view_call_input)?
+        .write(&(poap, binding, format, signature, poap_indices))?
+        .bytes();
+
+    Ok(input)
+}
+
+/// Builds the inputs of the `poap_ownership` and `owner_signature` guests.
+fn get_composition_inputs(
+    rpc_url: &str,
+    poap: &PoapContract,
+    binding: &GroupBinding,
+    format: &MessageFormat,
+    signature: &[u8],
+) -> Result<CompositionInputs> {
+    // The ownership guest is given the owner directly, the signature guest proves it signed.
+    let owner = recover_owner(signature, &binding.signing_hash(format))?;
+
+    let env = EthViewCallEnv::from_rpc(rpc_url, None)?.with_chain_spec(poap.chain_spec()?);
+    let number = env.header().number();
+    let poap_index = find_poap_index(rpc_url, number, poap, owner, binding.event_id)?;
+    let call = POAP::tokenDetailsOfOwnerByIndexCall {
+        owner,
+        index: poap_index,
+    };
+    let (view_call_input, returns) = ViewCall::new(call, poap.address)
+        .with_caller(owner)
+        .preflight(env)?;
+    ensure!(
+        returns._1 == binding.event_id,
+        "POAP at index {} is from event {}, not from event {}",
+        poap_index,
+        returns._1,
+        binding.event_id
+    );
+    println!("Nullifier: {}", binding.nullifier(owner, returns._0));
+
+    let ownership = InputBuilder::new()
+        .write(&view_call_input)?
+        .write((poap, owner, binding.event_id, poap_index))?
+        .bytes();
+    let signature = InputBuilder::new()
+        .write((binding, format, signature))?
+        .bytes();
+
+    Ok(CompositionInputs {
+        ownership,
+        signature,
+    })
+}
+
 pub struct InputBuilder {
     input: Vec<u32>,
 }
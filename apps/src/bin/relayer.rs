@@ -0,0 +1,69 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use apps::{
+    relayer::{self, RateLimiter, RelayerState},
+    signer::SignerArgs,
+    tx::FeeArgs,
+    TxSender,
+};
+use clap::Parser;
+
+/// Arguments of the relayer CLI.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Ethereum chain ID
+    #[clap(long)]
+    chain_id: u64,
+
+    /// Ethereum Node endpoint.
+    #[arg(short, long, env = "RPC_URL")]
+    rpc_url: String,
+
+    /// Address of the `POAPGroup` the proofs are submitted to.
+    #[clap(long)]
+    contract: String,
+
+    #[clap(flatten)]
+    wallet: SignerArgs,
+
+    #[clap(flatten)]
+    fees: FeeArgs,
+
+    /// Port the API is served on.
+    #[clap(long, default_value_t = 8080)]
+    port: u16,
+
+    /// Maximum number of submissions of each client IP address per minute.
+    #[clap(long, default_value_t = 10)]
+    rate_limit: u32,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+
+    // The wallet of the relayer pays for the transactions of all holders.
+    let signer = args.wallet.signer(args.chain_id).await?;
+    let sender = TxSender::with_signer(args.chain_id, &args.rpc_url, signer, &args.contract)?
+        .with_fees(args.fees.try_into()?);
+
+    let limiter = RateLimiter::new(args.rate_limit, Duration::from_secs(60));
+    relayer::serve(RelayerState::new(sender, limiter), args.port).await
+}
@@ -0,0 +1,760 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Asynchronous proving on Bonsai, polling with backoff until a deadline or a cancellation, and
+//! resumable from a state file.
+
+use std::{
+    fs::File,
+    future::Future,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Mutex, Once},
+    time::{Duration, Instant},
+};
+
+use alloy_primitives::FixedBytes;
+use anyhow::Context;
+use bonsai_sdk::{
+    alpha::{Client, SdkErr, SessionId, SnarkId},
+    alpha_async as bonsai_sdk,
+};
+use clap::Args;
+use risc0_ethereum_contracts::groth16::Seal;
+use risc0_zkvm::{compute_image_id, Receipt};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+use crate::Snark;
+
+/// The errors of a Bonsai proof request.
+#[derive(Debug, Error)]
+pub enum BonsaiError {
+    #[error("Bonsai job {uuid} failed: {message}")]
+    Failed { uuid: String, message: String },
+    #[error("Bonsai job {uuid} timed out: {message}")]
+    TimedOut { uuid: String, message: String },
+    #[error("Bonsai job {uuid} was aborted: {message}")]
+    Aborted { uuid: String, message: String },
+    #[error("Bonsai job {uuid} has unexpected status {status}: {message}")]
+    UnexpectedStatus {
+        uuid: String,
+        status: String,
+        message: String,
+    },
+    #[error("no proof within {0:?}")]
+    DeadlineExceeded(Duration),
+    #[error("proof request was cancelled")]
+    Cancelled,
+    #[error(
+        "state file {} tracks the unfinished session {uuid}; resume it with `prove --resume` or \
+         remove the file",
+        path.display()
+    )]
+    UnfinishedState { path: PathBuf, uuid: String },
+    #[error(transparent)]
+    Sdk(#[from] SdkErr),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl BonsaiError {
+    /// Returns the error of a job that ended with the given status other than `SUCCEEDED`.
+    fn terminal(uuid: &str, status: &str, error_msg: Option<String>) -> Self {
+        let uuid = uuid.to_string();
+        let message = error_msg.unwrap_or_default();
+        match status {
+            "FAILED" => BonsaiError::Failed { uuid, message },
+            "TIMED_OUT" => BonsaiError::TimedOut { uuid, message },
+            "ABORTED" => BonsaiError::Aborted { uuid, message },
+            status => BonsaiError::UnexpectedStatus {
+                uuid,
+                status: status.to_string(),
+                message,
+            },
+        }
+    }
+}
+
+/// How the status of Bonsai jobs is polled.
+#[derive(Debug, Clone)]
+pub struct PollConfig {
+    /// Delay before the first status request after a job was created.
+    pub interval: Duration,
+    /// The delay is doubled after each request, up to this maximum.
+    pub max_interval: Duration,
+    /// Overall deadline of the proof request, including the snark.
+    pub timeout: Duration,
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            max_interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(4 * 3600),
+        }
+    }
+}
+
+/// Command line arguments configuring the polling of Bonsai.
+#[derive(Args, Debug, Clone)]
+pub struct PollArgs {
+    /// Initial delay in seconds between Bonsai status requests, doubled after each request.
+    #[clap(long, default_value_t = 5)]
+    pub bonsai_poll_interval: u64,
+
+    /// Maximum delay in seconds between Bonsai status requests.
+    #[clap(long, default_value_t = 60)]
+    pub bonsai_max_poll_interval: u64,
+
+    /// Deadline in seconds of a Bonsai proof request.
+    #[clap(long, default_value_t = 4 * 3600)]
+    pub bonsai_timeout: u64,
+}
+
+impl From<PollArgs> for PollConfig {
+    fn from(args: PollArgs) -> Self {
+        Self {
+            interval: Duration::from_secs(args.bonsai_poll_interval),
+            max_interval: Duration::from_secs(args.bonsai_max_poll_interval),
+            timeout: Duration::from_secs(args.bonsai_timeout),
+        }
+    }
+}
+
+/// The state of a polled Bonsai job.
+enum JobState<T> {
+    /// The job is still running, in the given proving state if known.
+    Running(Option<String>),
+    Succeeded(T),
+}
+
+/// The progress of a Bonsai proof request, saved after each stage so that a request whose process
+/// died can be resumed without paying for a new session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofState {
+    /// Image ID of the proven guest.
+    pub image_id: String,
+    /// ID of the uploaded input.
+    pub input_id: String,
+    /// IDs of the uploaded assumption receipts.
+    pub assumption_ids: Vec<String>,
+    /// UUID of the session, once created.
+    pub session_uuid: Option<String>,
+    /// Receipt of the session, once downloaded.
+    pub receipt: Option<Receipt>,
+    /// UUID of the snark conversion, once created.
+    pub snark_uuid: Option<String>,
+    /// Whether the snark was received, after which the state may be replaced by a new request.
+    #[serde(default)]
+    pub completed: bool,
+}
+
+impl ProofState {
+    /// Returns the UUID of the session or snark conversion of an unfinished request, which would
+    /// be lost if the state was replaced.
+    pub fn unfinished_job(&self) -> Option<&str> {
+        if self.completed {
+            return None;
+        }
+        self.snark_uuid.as_deref().or(self.session_uuid.as_deref())
+    }
+
+    /// Loads the state from a JSON file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open state file {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid state file {}", path.display()))
+    }
+
+    /// Saves the state to a JSON file, replacing it atomically.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)
+            .with_context(|| format!("failed to write state file {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("failed to write state file {}", path.display()))
+    }
+}
+
+/// The token of the proof request in progress, cancelled on Ctrl-C.
+static CTRL_C_TOKEN: Mutex<Option<CancellationToken>> = Mutex::new(None);
+static CTRL_C_HANDLER: Once = Once::new();
+
+/// Cancels the token of the returned guard on Ctrl-C, until the guard is dropped.
+///
+/// This is for blocking callers whose runtime only lives as long as a proof request. The handler
+/// replaces the default of terminating the process for the rest of its life, so a Ctrl-C with no
+/// proof request in progress, or a second one, still exits.
+pub fn cancel_on_ctrl_c() -> CtrlCGuard {
+    CTRL_C_HANDLER.call_once(|| {
+        std::thread::spawn(|| {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to start the Ctrl-C runtime");
+            runtime.block_on(async {
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    match CTRL_C_TOKEN.lock().unwrap().take() {
+                        Some(token) => {
+                            log::warn!("Stopped polling; the Bonsai session keeps running");
+                            token.cancel();
+                        }
+                        None => std::process::exit(130),
+                    }
+                }
+            });
+        });
+    });
+
+    let token = CancellationToken::new();
+    *CTRL_C_TOKEN.lock().unwrap() = Some(token.clone());
+    CtrlCGuard(token)
+}
+
+/// Keeps a token cancelled on Ctrl-C, see [cancel_on_ctrl_c].
+pub struct CtrlCGuard(CancellationToken);
+
+impl CtrlCGuard {
+    /// Returns the token cancelled on Ctrl-C.
+    pub fn token(&self) -> CancellationToken {
+        self.0.clone()
+    }
+}
+
+impl Drop for CtrlCGuard {
+    fn drop(&mut self) {
+        *CTRL_C_TOKEN.lock().unwrap() = None;
+    }
+}
+
+/// A prover sending proof requests to Bonsai without blocking the runtime.
+///
+/// Cancelling the token stops polling; the Bonsai session itself keeps running, and can be
+/// resumed from the state file.
+pub struct AsyncBonsaiProver {
+    poll: PollConfig,
+    cancel: CancellationToken,
+    state_file: Option<PathBuf>,
+    client: Option<Client>,
+}
+
+impl AsyncBonsaiProver {
+    /// Creates a new `AsyncBonsaiProver`, cancelled through the given token.
+    pub fn new(poll: PollConfig, cancel: CancellationToken) -> Self {
+        Self {
+            poll,
+            cancel,
+            state_file: None,
+            client: None,
+        }
+    }
+
+    /// Saves the progress of each proof request up to a snark to the given file.
+    ///
+    /// A new request fails rather than replace a state of an unfinished request.
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_file = Some(path);
+        self
+    }
+
+    /// Sends the requests with the given client, instead of one configured from the
+    /// `BONSAI_API_URL` and `BONSAI_API_KEY` environment variables.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Proves the given elf and input without a snark, resolving its assumptions with the given
+    /// receipts.
+    ///
+    /// These are the sub-proofs of a composition, so they are not saved to the state file, which
+    /// only tracks the request of the snark that is submitted.
+    pub async fn prove_receipt(
+        &self,
+        elf: &[u8],
+        input: &[u8],
+        assumptions: &[Receipt],
+    ) -> Result<Receipt, BonsaiError> {
+        let prover = AsyncBonsaiProver {
+            poll: self.poll.clone(),
+            cancel: self.cancel.clone(),
+            state_file: None,
+            client: self.client.clone(),
+        };
+        let deadline = Instant::now() + prover.poll.timeout;
+        let client = prover.client().await?;
+        let mut state = prover.upload(&client, elf, input, assumptions).await?;
+        prover.session_receipt(&client, deadline, &mut state).await
+    }
+
+    /// Generates a snark proof for the given elf and input, resolving its assumptions with the
+    /// given receipts, and also returns the receipt of the session.
+    pub async fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        input: &[u8],
+        assumptions: &[Receipt],
+    ) -> Result<(Receipt, Snark), BonsaiError> {
+        let deadline = Instant::now() + self.poll.timeout;
+        let client = self.client().await?;
+        let mut state = self.upload(&client, elf, input, assumptions).await?;
+        let receipt = self.session_receipt(&client, deadline, &mut state).await?;
+        let snark = self.snark(&client, deadline, &mut state).await?;
+        Ok((receipt, snark))
+    }
+
+    /// Resumes a proof request from its state, continuing after its last completed stage up to
+    /// the snark proof.
+    pub async fn resume(&self, mut state: ProofState) -> Result<(Receipt, Snark), BonsaiError> {
+        let deadline = Instant::now() + self.poll.timeout;
+        let client = self.client().await?;
+        let receipt = self.session_receipt(&client, deadline, &mut state).await?;
+        let snark = self.snark(&client, deadline, &mut state).await?;
+        Ok((receipt, snark))
+    }
+
+    /// Returns the client given with [AsyncBonsaiProver::with_client], or one configured from the
+    /// environment.
+    async fn client(&self) -> Result<Client, BonsaiError> {
+        match &self.client {
+            Some(client) => Ok(client.clone()),
+            None => Ok(bonsai_sdk::get_client_from_env(risc0_zkvm::VERSION).await?),
+        }
+    }
+
+    /// Uploads the elf, the input and the assumptions, and returns the initial state.
+    async fn upload(
+        &self,
+        client: &Client,
+        elf: &[u8],
+        input: &[u8],
+        assumptions: &[Receipt],
+    ) -> Result<ProofState, BonsaiError> {
+        // Fail before uploading anything if the state file tracks a session that was not resumed.
+        if let Some(path) = &self.state_file {
+            if path.exists() {
+                if let Some(uuid) = ProofState::load(path)?.unfinished_job() {
+                    return Err(BonsaiError::UnfinishedState {
+                        path: path.clone(),
+                        uuid: uuid.to_string(),
+                    });
+                }
+            }
+        }
+
+        // Compute the image_id, then upload the ELF with the image_id as its key.
+        let image_id = compute_image_id(elf)?;
+        let image_id_hex = image_id.to_string();
+        bonsai_sdk::upload_img(client.clone(), image_id_hex.clone(), elf.to_vec()).await?;
+        log::info!("Image ID: 0x{}", image_id_hex);
+
+        // Prepare input data and upload it.
+        let input_id = bonsai_sdk::upload_input(client.clone(), input.to_vec()).await?;
+
+        // Upload the receipts of the assumptions.
+        let mut assumption_ids = Vec::with_capacity(assumptions.len());
+        for receipt in assumptions {
+            let buf = bincode::serialize(receipt).context("failed to serialize assumption")?;
+            assumption_ids.push(bonsai_sdk::upload_receipt(client.clone(), buf).await?);
+        }
+
+        let state = ProofState {
+            image_id: image_id_hex,
+            input_id,
+            assumption_ids,
+            session_uuid: None,
+            receipt: None,
+            snark_uuid: None,
+            completed: false,
+        };
+        self.save(&state)?;
+        Ok(state)
+    }
+
+    /// Creates the session unless it exists, and waits for its receipt unless it was downloaded.
+    async fn session_receipt(
+        &self,
+        client: &Client,
+        deadline: Instant,
+        state: &mut ProofState,
+    ) -> Result<Receipt, BonsaiError> {
+        if let Some(receipt) = &state.receipt {
+            return Ok(receipt.clone());
+        }
+
+        // Start a session running the prover.
+        let session = match &state.session_uuid {
+            Some(uuid) => SessionId::new(uuid.clone()),
+            None => {
+                let session = bonsai_sdk::create_session(
+                    client.clone(),
+                    state.image_id.clone(),
+                    state.input_id.clone(),
+                    state.assumption_ids.clone(),
+                )
+                .await?;
+                log::info!("Created session: {}", session.uuid);
+                state.session_uuid = Some(session.uuid.clone());
+                self.save(state)?;
+                session
+            }
+        };
+
+        let receipt_url = self
+            .wait(&session.uuid, deadline, || async {
+                let res = bonsai_sdk::session_status(client.clone(), session.clone()).await?;
+                match res.status.as_str() {
+                    "RUNNING" => Ok(JobState::Running(res.state)),
+                    "SUCCEEDED" => {
+                        let receipt_url = res
+                            .receipt_url
+                            .context("API error, missing receipt on completed session")?;
+                        Ok(JobState::Succeeded(receipt_url))
+                    }
+                    status => Err(BonsaiError::terminal(&session.uuid, status, res.error_msg)),
+                }
+            })
+            .await?;
+
+        // Download the receipt, containing the output.
+        let receipt_buf = bonsai_sdk::download(client.clone(), receipt_url).await?;
+        let receipt: Receipt =
+            bincode::deserialize(&receipt_buf).context("failed to deserialize receipt")?;
+        state.receipt = Some(receipt.clone());
+        self.save(state)?;
+
+        Ok(receipt)
+    }
+
+    /// Creates the snark conversion of the session unless it exists, and waits for the snark.
+    async fn snark(
+        &self,
+        client: &Client,
+        deadline: Instant,
+        state: &mut ProofState,
+    ) -> Result<Snark, BonsaiError> {
+        let snark = match (&state.snark_uuid, &state.session_uuid) {
+            (Some(uuid), _) => SnarkId::new(uuid.clone()),
+            (None, Some(session_uuid)) => {
+                let snark = bonsai_sdk::create_snark(client.clone(), session_uuid.clone()).await?;
+                log::info!("Created snark session: {}", snark.uuid);
+                state.snark_uuid = Some(snark.uuid.clone());
+                self.save(state)?;
+                snark
+            }
+            (None, None) => return Err(anyhow::anyhow!("no session to convert to a snark").into()),
+        };
+
+        let snark_receipt = self
+            .wait(&snark.uuid, deadline, || async {
+                let res = bonsai_sdk::snark_status(client.clone(), snark.clone()).await?;
+                match res.status.as_str() {
+                    "RUNNING" => Ok(JobState::Running(None)),
+                    "SUCCEEDED" => {
+                        let output = res.output.context("API error, missing snark output")?;
+                        Ok(JobState::Succeeded(output))
+                    }
+                    status => Err(BonsaiError::terminal(&snark.uuid, status, res.error_msg)),
+                }
+            })
+            .await?;
+
+        let snark = snark_receipt.snark;
+        log::debug!("Snark proof!: {snark:?}");
+
+        let seal = Seal::abi_encode(snark).context("Read seal")?;
+        let post_state_digest: FixedBytes<32> = snark_receipt
+            .post_state_digest
+            .as_slice()
+            .try_into()
+            .context("Read post_state_digest")?;
+        let journal = snark_receipt.journal;
+        state.completed = true;
+        self.save(state)?;
+
+        Ok((journal, post_state_digest, seal))
+    }
+
+    /// Saves the state to the state file, if any.
+    fn save(&self, state: &ProofState) -> anyhow::Result<()> {
+        match &self.state_file {
+            Some(path) => state.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Polls the status of a job with backoff until it succeeds, fails, passes the deadline or is
+    /// cancelled.
+    async fn wait<T, F, Fut>(
+        &self,
+        uuid: &str,
+        deadline: Instant,
+        mut status: F,
+    ) -> Result<T, BonsaiError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<JobState<T>, BonsaiError>>,
+    {
+        let mut interval = self.poll.interval;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(BonsaiError::DeadlineExceeded(self.poll.timeout));
+            }
+            tokio::select! {
+                _ = self.cancel.cancelled() => return Err(BonsaiError::Cancelled),
+                _ = tokio::time::sleep(interval.min(remaining)) => {}
+            }
+
+            match status().await? {
+                JobState::Succeeded(output) => return Ok(output),
+                JobState::Running(state) => log::info!(
+                    "Job {} is running - state: {} - continue polling...",
+                    uuid,
+                    state.unwrap_or_default()
+                ),
+            }
+            interval = (interval * 2).min(self.poll.max_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use risc0_zkvm::{sha::Digest, ExitCode, InnerReceipt, MaybePruned, ReceiptClaim};
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    fn prover(timeout: Duration) -> AsyncBonsaiProver {
+        let poll = PollConfig {
+            interval: Duration::from_millis(1),
+            max_interval: Duration::from_millis(4),
+            timeout,
+        };
+        AsyncBonsaiProver::new(poll, CancellationToken::new())
+    }
+
+    #[test]
+    fn terminal_status() {
+        let err = BonsaiError::terminal("uuid", "TIMED_OUT", Some("too slow".to_string()));
+        assert!(matches!(err, BonsaiError::TimedOut { ref message, .. } if message == "too slow"));
+        assert!(matches!(
+            BonsaiError::terminal("uuid", "ABORTED", None),
+            BonsaiError::Aborted { .. }
+        ));
+        assert!(matches!(
+            BonsaiError::terminal("uuid", "UNKNOWN", None),
+            BonsaiError::UnexpectedStatus { .. }
+        ));
+    }
+
+    #[test]
+    fn proof_state_round_trip() {
+        let path = std::env::temp_dir().join("zkpoap-proof-state-test.json");
+        let state = ProofState {
+            image_id: "image".to_string(),
+            input_id: "input".to_string(),
+            assumption_ids: vec!["assumption".to_string()],
+            session_uuid: Some("session".to_string()),
+            receipt: None,
+            snark_uuid: None,
+            completed: false,
+        };
+        state.save(&path).unwrap();
+
+        let loaded = ProofState::load(&path).unwrap();
+        assert_eq!(loaded.session_uuid.as_deref(), Some("session"));
+        assert_eq!(loaded.assumption_ids, state.assumption_ids);
+        assert!(loaded.snark_uuid.is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Returns a state of a request whose process died after creating the session.
+    fn unfinished_state() -> ProofState {
+        ProofState {
+            image_id: "image".to_string(),
+            input_id: "input".to_string(),
+            assumption_ids: vec![],
+            session_uuid: Some("session".to_string()),
+            receipt: None,
+            snark_uuid: None,
+            completed: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn unfinished_state_is_not_replaced() {
+        let path = std::env::temp_dir().join("zkpoap-proof-state-unfinished-test.json");
+        unfinished_state().save(&path).unwrap();
+
+        // The state file is checked before anything is uploaded.
+        let client = bonsai_sdk::get_client_from_parts(
+            "http://127.0.0.1:1".to_string(),
+            "key".to_string(),
+            risc0_zkvm::VERSION,
+        )
+        .await
+        .unwrap();
+        let prover = prover(Duration::from_secs(10))
+            .with_state_file(path.clone())
+            .with_client(client);
+        let err = prover
+            .prove_with_assumptions(&[], &[], &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BonsaiError::UnfinishedState { ref uuid, .. } if uuid == "session"));
+
+        let mut completed = unfinished_state();
+        completed.completed = true;
+        assert!(completed.unfinished_job().is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_unfinished_session() {
+        let journal = vec![1u8, 2, 3];
+        let receipt = Receipt::new(
+            InnerReceipt::Fake {
+                claim: ReceiptClaim {
+                    pre: MaybePruned::Pruned(Digest::ZERO),
+                    post: MaybePruned::Pruned(Digest::ZERO),
+                    exit_code: ExitCode::Halted(0),
+                    input: Digest::ZERO,
+                    output: None.into(),
+                },
+            },
+            journal.clone(),
+        );
+
+        let server = MockServer::start().await;
+        // The session of the state must be polled, never created again.
+        Mock::given(method("POST"))
+            .and(path("/sessions/create"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/sessions/status/session"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "status": "SUCCEEDED",
+                "receipt_url": format!("{}/receipts/session", server.uri()),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/receipts/session"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_bytes(bincode::serialize(&receipt).unwrap()),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/snark/create"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "uuid": "snark" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/snark/status/snark"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "status": "SUCCEEDED",
+                "output": {
+                    "snark": {
+                        "a": [[1], [2]],
+                        "b": [[[3], [4]], [[5], [6]]],
+                        "c": [[7], [8]],
+                    },
+                    "post_state_digest": [0; 32],
+                    "journal": journal,
+                },
+            })))
+            .mount(&server)
+            .await;
+
+        let path = std::env::temp_dir().join("zkpoap-proof-state-resume-test.json");
+        let client =
+            bonsai_sdk::get_client_from_parts(server.uri(), "key".to_string(), risc0_zkvm::VERSION)
+                .await
+                .unwrap();
+        let prover = prover(Duration::from_secs(10))
+            .with_state_file(path.clone())
+            .with_client(client);
+        let (resumed, (snark_journal, _, seal)) = prover.resume(unfinished_state()).await.unwrap();
+        assert_eq!(resumed.journal.bytes, journal);
+        assert_eq!(snark_journal, journal);
+        assert!(!seal.is_empty());
+
+        // Every stage is recorded, and the finished state may be replaced.
+        let state = ProofState::load(&path).unwrap();
+        assert!(state.receipt.is_some());
+        assert_eq!(state.snark_uuid.as_deref(), Some("snark"));
+        assert!(state.unfinished_job().is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn wait_until_succeeded() {
+        let prover = prover(Duration::from_secs(10));
+        let mut polls = 0;
+        let output = prover
+            .wait("uuid", Instant::now() + prover.poll.timeout, || {
+                polls += 1;
+                let state = if polls < 3 {
+                    JobState::Running(None)
+                } else {
+                    JobState::Succeeded(polls)
+                };
+                async move { Ok(state) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(output, 3);
+    }
+
+    #[tokio::test]
+    async fn wait_deadline() {
+        let prover = prover(Duration::from_millis(20));
+        let err = prover
+            .wait("uuid", Instant::now() + prover.poll.timeout, || async {
+                Ok(JobState::<()>::Running(None))
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BonsaiError::DeadlineExceeded(_)));
+    }
+
+    #[tokio::test]
+    async fn wait_cancelled() {
+        let prover = prover(Duration::from_secs(10));
+        prover.cancel.cancel();
+        let err = prover
+            .wait("uuid", Instant::now() + prover.poll.timeout, || async {
+                Ok(JobState::<()>::Running(None))
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BonsaiError::Cancelled));
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Composition of the `poap_ownership` and `owner_signature` receipts into a proof of the
+//! `compose_poap_owner` guest, accepted by `POAPGroup.joinGroupComposed`.
+
+use anyhow::{Context, Result};
+use methods::{
+    COMPOSE_POAP_OWNER_ELF, OWNER_SIGNATURE_ELF, OWNER_SIGNATURE_ID, POAP_OWNERSHIP_ELF,
+    POAP_OWNERSHIP_ID,
+};
+use risc0_zkvm::{serde::to_vec, sha::Digest, Receipt};
+
+use crate::{guest, Prover, Snark};
+
+/// The inputs of the composed guests.
+pub struct CompositionInputs {
+    /// Input of `poap_ownership`: the view call input, then the POAP contract, the owner, the
+    /// event ID and the index of the POAP.
+    pub ownership: Vec<u8>,
+    /// Input of `owner_signature`: the group binding, the message format and the signature.
+    pub signature: Vec<u8>,
+}
+
+impl CompositionInputs {
+    /// Executes both guests locally, so that rejected inputs fail before requesting a proof.
+    pub fn execute(&self) -> Result<()> {
+        guest::execute(POAP_OWNERSHIP_ELF, &self.ownership).context("ownership guest failed")?;
+        guest::execute(OWNER_SIGNATURE_ELF, &self.signature).context("signature guest failed")?;
+        Ok(())
+    }
+}
+
+/// Returns the input of the `compose_poap_owner` guest, which verifies the journals of the given
+/// receipts.
+pub fn compose_input(ownership: &Receipt, signature: &Receipt) -> Result<Vec<u8>> {
+    let input = to_vec(&(
+        Digest::from(POAP_OWNERSHIP_ID),
+        Digest::from(OWNER_SIGNATURE_ID),
+        &ownership.journal.bytes,
+        &signature.journal.bytes,
+    ))?;
+    Ok(bytemuck::cast_slice(&input).to_vec())
+}
+
+/// Proves both guests and checks their composition with the local executor, returning the input
+/// and the assumptions of the composed guest.
+fn prove_parts(prover: &dyn Prover, inputs: &CompositionInputs) -> Result<(Vec<u8>, [Receipt; 2])> {
+    inputs.execute()?;
+
+    let ownership = prover.prove_receipt(POAP_OWNERSHIP_ELF, &inputs.ownership, &[])?;
+    let signature = prover.prove_receipt(OWNER_SIGNATURE_ELF, &inputs.signature, &[])?;
+
+    // Run the composition locally first, so that mismatching receipts fail before proving.
+    let input = compose_input(&ownership, &signature)?;
+    let assumptions = [ownership, signature];
+    guest::execute_with_assumptions(COMPOSE_POAP_OWNER_ELF, &input, &assumptions)?;
+
+    Ok((input, assumptions))
+}
+
+/// Proves both guests and composes their receipts into a receipt without a snark.
+///
+/// With the dev-mode prover all receipts are fake, which allows testing the composition without
+/// proving.
+pub fn prove_receipt(prover: &dyn Prover, inputs: &CompositionInputs) -> Result<Receipt> {
+    let (input, assumptions) = prove_parts(prover, inputs)?;
+    prover.prove_receipt(COMPOSE_POAP_OWNER_ELF, &input, &assumptions)
+}
+
+/// Proves both guests and composes their receipts, returning the snark proof of the composed
+/// guest.
+pub fn prove(prover: &dyn Prover, inputs: &CompositionInputs) -> Result<Snark> {
+    let (input, assumptions) = prove_parts(prover, inputs)?;
+    let (_receipt, snark) =
+        prover.prove_with_assumptions(COMPOSE_POAP_OWNER_ELF, &input, &assumptions)?;
+    Ok(snark)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Address, B256, U256};
+    use k256::ecdsa::SigningKey;
+    use methods::COMPOSE_POAP_OWNER_ID;
+    use risc0_ethereum_view_call::{ethereum::EthViewCallEnv, EvmHeader, ViewCall};
+    use zkpoap_types::{
+        GroupBinding, GuestError, MessageFormat, NullifierMode, PoapContract, SemaphoreIdentity,
+        POAP,
+    };
+
+    use super::*;
+    use crate::{
+        journal::ComposedJournal,
+        message::sign,
+        poap::{find_poap_index, POAP_CONTRACT},
+        DevModeProver,
+    };
+
+    // Like the tests of the guests, these query the POAP of `POAP_OWNER_PRIVATE_KEY` from event
+    // `POAP_EVENT_ID` on Gnosis Chain through `RPC_URL`.
+    const GNOSIS_POAP: PoapContract = PoapContract {
+        address: POAP_CONTRACT,
+        chain_id: 100,
+    };
+
+    fn owner_key() -> SigningKey {
+        let private_key = std::env::var("POAP_OWNER_PRIVATE_KEY").unwrap();
+        SigningKey::from_slice(&hex::decode(private_key.trim_start_matches("0x")).unwrap()).unwrap()
+    }
+
+    fn binding(event_id: U256) -> GroupBinding {
+        GroupBinding {
+            event_id,
+            group_contract: Address::repeat_byte(1),
+            chain_id: 100,
+            semaphore_identity: SemaphoreIdentity::Commitment(U256::from(1)),
+            nullifier_mode: NullifierMode::Address,
+        }
+    }
+
+    fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+        bytemuck::cast_slice(words).to_vec()
+    }
+
+    /// Builds the input of `poap_ownership` as the publisher does, for the latest block.
+    fn ownership_input(owner: Address, event_id: U256) -> Vec<u8> {
+        let rpc_url = std::env::var("RPC_URL").unwrap();
+        let env = EthViewCallEnv::from_rpc(&rpc_url, None)
+            .unwrap()
+            .with_chain_spec(GNOSIS_POAP.chain_spec().unwrap());
+        let number = env.header().number();
+        let index = find_poap_index(&rpc_url, number, &GNOSIS_POAP, owner, event_id).unwrap();
+        let call = POAP::tokenDetailsOfOwnerByIndexCall { owner, index };
+        let (view_call_input, _) = ViewCall::new(call, GNOSIS_POAP.address)
+            .with_caller(owner)
+            .preflight(env)
+            .unwrap();
+
+        let mut input = to_vec(&view_call_input).unwrap();
+        input.extend(to_vec(&(GNOSIS_POAP, owner, event_id, index)).unwrap());
+        words_to_bytes(&input)
+    }
+
+    /// Builds the input of `owner_signature` for the message signed with the given key.
+    fn signature_input(binding: &GroupBinding, format: MessageFormat, key: &SigningKey) -> Vec<u8> {
+        let signature = sign(binding.signing_hash(&format), key).unwrap();
+        words_to_bytes(&to_vec(&(binding, format, signature)).unwrap())
+    }
+
+    /// Composes the receipts of the inputs with the dev-mode prover.
+    fn compose(inputs: &CompositionInputs) -> Result<Receipt> {
+        std::env::set_var("RISC0_DEV_MODE", "1");
+        prove_receipt(&DevModeProver {}, inputs)
+    }
+
+    fn guest_error(err: anyhow::Error) -> GuestError {
+        err.downcast::<GuestError>().unwrap()
+    }
+
+    #[test]
+    fn compose_poap_owner() {
+        let key = owner_key();
+        let owner = Address::from_public_key(key.verifying_key());
+        let binding = binding(std::env::var("POAP_EVENT_ID").unwrap().parse().unwrap());
+        let inputs = CompositionInputs {
+            ownership: ownership_input(owner, binding.event_id),
+            signature: signature_input(&binding, MessageFormat::Eip191, &key),
+        };
+
+        let receipt = compose(&inputs).unwrap();
+        receipt.verify(COMPOSE_POAP_OWNER_ID).unwrap();
+
+        let journal = ComposedJournal::decode(&receipt.journal.bytes).unwrap();
+        let proof_data = &journal.journal.proof_data;
+        assert_eq!(proof_data.eventId, binding.event_id);
+        assert_eq!(proof_data.semaphoreId, U256::from(1));
+        assert_eq!(proof_data.groupContract, binding.group_contract);
+        assert_eq!(proof_data.nullifier, binding.nullifier(owner, U256::ZERO));
+        assert_eq!(proof_data.poapContract, GNOSIS_POAP.address);
+        // The composed guests are identified by their image IDs.
+        assert_eq!(
+            journal.ownership_image_id,
+            B256::from_slice(Digest::from(POAP_OWNERSHIP_ID).as_bytes())
+        );
+        assert_eq!(
+            journal.signature_image_id,
+            B256::from_slice(Digest::from(OWNER_SIGNATURE_ID).as_bytes())
+        );
+    }
+
+    #[test]
+    fn compose_rejects_other_signer() {
+        let owner = Address::from_public_key(owner_key().verifying_key());
+        let binding = binding(std::env::var("POAP_EVENT_ID").unwrap().parse().unwrap());
+        // The well-known key `1` does not hold the POAP proven by the ownership receipt.
+        let other_key = SigningKey::from_slice(&U256::from(1).to_be_bytes::<32>()).unwrap();
+        let inputs = CompositionInputs {
+            ownership: ownership_input(owner, binding.event_id),
+            signature: signature_input(&binding, MessageFormat::Eip191, &other_key),
+        };
+
+        let err = guest_error(compose(&inputs).unwrap_err());
+        assert!(matches!(err, GuestError::WrongSigner(signer) if signer != owner));
+    }
+
+    #[test]
+    fn compose_rejects_other_event() {
+        let key = owner_key();
+        let owner = Address::from_public_key(key.verifying_key());
+        let event_id: U256 = std::env::var("POAP_EVENT_ID").unwrap().parse().unwrap();
+        // The owner signs a message for another event than the one of its POAP.
+        let inputs = CompositionInputs {
+            ownership: ownership_input(owner, event_id),
+            signature: signature_input(
+                &binding(event_id + U256::from(1)),
+                MessageFormat::Eip191,
+                &key,
+            ),
+        };
+
+        let err = guest_error(compose(&inputs).unwrap_err());
+        assert!(matches!(err, GuestError::WrongEvent(id) if id == event_id));
+    }
+
+    #[test]
+    fn compose_rejects_expired_signature() {
+        let key = owner_key();
+        let owner = Address::from_public_key(key.verifying_key());
+        let binding = binding(std::env::var("POAP_EVENT_ID").unwrap().parse().unwrap());
+        // The deadline passed long before the block the ownership is proven at.
+        let format = MessageFormat::Eip712 {
+            deadline: U256::from(1),
+        };
+        let inputs = CompositionInputs {
+            ownership: ownership_input(owner, binding.event_id),
+            signature: signature_input(&binding, format, &key),
+        };
+
+        let err = guest_error(compose(&inputs).unwrap_err());
+        assert!(matches!(err, GuestError::SignatureExpired));
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline verification of Groth16 seals, as done on-chain by `RiscZeroGroth16Verifier.verify`.
+
+use alloy_primitives::{B256, U256};
+use alloy_sol_types::SolValue;
+use anyhow::{anyhow, ensure, Context, Result};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ff::{BigInt, PrimeField};
+use ark_groth16::{prepare_verifying_key, Groth16, Proof, VerifyingKey};
+use risc0_ethereum_contracts::groth16::Seal;
+use risc0_zkvm::{
+    sha::{Digest, Digestible},
+    ExitCode, MaybePruned, Output, ReceiptClaim,
+};
+use thiserror::Error;
+
+/// Control IDs of the recursion circuits accepted by the verifier, as in `ControlID.sol`.
+const CONTROL_ID_0: u128 = 0x51a3d73938c3681118ba0a2549f7c188;
+const CONTROL_ID_1: u128 = 0x44f39e6e6cef91de6d743e7f5b7a1e67;
+const BN254_CONTROL_ID: &str = "0x10ff834dbef62ccbba201ecd26a772e3036a075aacbaf47200679a11dcdcf10d";
+
+// Verifying key of `Groth16Verifier.sol`. The coordinates of G2 points are in the order of the
+// pairing precompile, with the imaginary part first.
+const ALPHA: [&str; 2] = [
+    "20491192805390485299153009773594534940189261866228447918068658471970481763042",
+    "9383485363053290200918347156157836566562967994039712273449902621266178545958",
+];
+const BETA: [&str; 4] = [
+    "4252822878758300859123897981450591353533073413197771768651442665752259397132",
+    "6375614351688725206403948262868962793625744043794305715222011528459656738731",
+    "21847035105528745403288232691147584728191162732299865338377159692350059136679",
+    "10505242626370262277552901082094356697409835680220590971873171140371331206856",
+];
+const GAMMA: [&str; 4] = [
+    "11559732032986387107991004021392285783925812861821192530917403151452391805634",
+    "10857046999023057135944570762232829481370756359578518086990519993285655852781",
+    "4082367875863433681332203403145435568316851327593401208105741076214120093531",
+    "8495653923123431417604973247489272438418190587263600148770280649306958101930",
+];
+const DELTA: [&str; 4] = [
+    "17459137677540232029121579111806194201335604911445641118023073971023969565095",
+    "16850927772192893321067430466725055468022063819919435552018169508037555801891",
+    "16440269342816906375775882524040730637133399248560416660251195197654028701204",
+    "20318668496029522144912607185068507665315850570859328375503723843595483973858",
+];
+const IC: [[&str; 2]; 6] = [
+    [
+        "8446592859352799428420270221449902464741693648963397251242447530457567083492",
+        "1064796367193003797175961162477173481551615790032213185848276823815288302804",
+    ],
+    [
+        "3179835575189816632597428042194253779818690147323192973511715175294048485951",
+        "20895841676865356752879376687052266198216014795822152491318012491767775979074",
+    ],
+    [
+        "5332723250224941161709478398807683311971555792614491788690328996478511465287",
+        "21199491073419440416471372042641226693637837098357067793586556692319371762571",
+    ],
+    [
+        "12457994489566736295787256452575216703923664299075106359829199968023158780583",
+        "19706766271952591897761291684837117091856807401404423804318744964752784280790",
+    ],
+    [
+        "19617808913178163826953378459323299110911217259216006187355745713323154132237",
+        "21663537384585072695701846972542344484111393047775983928357046779215877070466",
+    ],
+    [
+        "6834578911681792552110317589222010969491336870276623105249474534788043166867",
+        "15060583660288623605191393599883223885678013570733629274538391874953353488393",
+    ],
+];
+
+/// Error returned when a seal is well-formed but does not prove the claim.
+#[derive(Debug, Error)]
+#[error("Groth16 seal does not verify for image ID {image_id}")]
+pub struct InvalidSeal {
+    pub image_id: Digest,
+}
+
+/// Verifies the ABI encoded Groth16 seal for the image ID, the post-state digest and the journal,
+/// as `RiscZeroGroth16Verifier.verify` does, so that an invalid proof is rejected before paying
+/// for the transaction.
+pub fn verify(
+    seal: &[u8],
+    image_id: impl Into<Digest>,
+    post_state_digest: B256,
+    journal: &[u8],
+) -> Result<()> {
+    let image_id = image_id.into();
+    let seal = Seal::abi_decode(seal, true).context("invalid Groth16 seal encoding")?;
+    let proof = Proof::<Bn254> {
+        a: g1(seal.a)?,
+        b: g2(seal.b[0], seal.b[1])?,
+        c: g1(seal.c)?,
+    };
+    let public_inputs = public_inputs(image_id, post_state_digest, journal)?;
+
+    let pvk = prepare_verifying_key(&verifying_key()?);
+    let valid = Groth16::<Bn254>::verify_proof(&pvk, &proof, &public_inputs)
+        .map_err(|err| anyhow!("failed to verify Groth16 proof: {}", err))?;
+    if !valid {
+        return Err(InvalidSeal { image_id }.into());
+    }
+    Ok(())
+}
+
+/// Returns the public inputs of the circuit: the control IDs and the digest of the claim that the
+/// guest with the image ID halted with exit code 0, the post-state digest and the journal.
+pub fn public_inputs(image_id: Digest, post_state_digest: B256, journal: &[u8]) -> Result<[Fr; 5]> {
+    let claim = ReceiptClaim {
+        pre: MaybePruned::Pruned(image_id),
+        post: MaybePruned::Pruned(Digest::from(post_state_digest.0)),
+        exit_code: ExitCode::Halted(0),
+        input: Digest::ZERO,
+        output: MaybePruned::Value(Some(Output {
+            journal: MaybePruned::Pruned(journal.digest()),
+            assumptions: MaybePruned::Pruned(Digest::ZERO),
+        })),
+    };
+    let (claim_0, claim_1) = split_digest(claim.digest());
+    let bn254_control_id = fr(BN254_CONTROL_ID.parse()?)?;
+
+    Ok([
+        Fr::from(CONTROL_ID_0),
+        Fr::from(CONTROL_ID_1),
+        claim_0,
+        claim_1,
+        bn254_control_id,
+    ])
+}
+
+/// Splits the digest into two 128-bit words, as `splitDigest` of the verifier: the digest is
+/// read as a little-endian integer, whose lower half comes first.
+fn split_digest(digest: Digest) -> (Fr, Fr) {
+    let (low, high) = digest.as_bytes().split_at(16);
+    (
+        Fr::from(u128::from_le_bytes(low.try_into().unwrap())),
+        Fr::from(u128::from_le_bytes(high.try_into().unwrap())),
+    )
+}
+
+fn verifying_key() -> Result<VerifyingKey<Bn254>> {
+    let parse = |values: &[&str]| -> Result<Vec<U256>> {
+        values
+            .iter()
+            .map(|value| {
+                value
+                    .parse::<U256>()
+                    .context("invalid verifying key constant")
+            })
+            .collect()
+    };
+    let point_g1 = |values: &[&str]| -> Result<G1Affine> {
+        let values = parse(values)?;
+        g1([values[0], values[1]])
+    };
+    let point_g2 = |values: &[&str]| -> Result<G2Affine> {
+        let values = parse(values)?;
+        g2([values[0], values[1]], [values[2], values[3]])
+    };
+
+    Ok(VerifyingKey {
+        alpha_g1: point_g1(&ALPHA)?,
+        beta_g2: point_g2(&BETA)?,
+        gamma_g2: point_g2(&GAMMA)?,
+        delta_g2: point_g2(&DELTA)?,
+        gamma_abc_g1: IC.iter().map(|ic| point_g1(ic)).collect::<Result<_>>()?,
+    })
+}
+
+fn fq(value: U256) -> Result<Fq> {
+    Fq::from_bigint(BigInt::new(value.into_limbs())).context("coordinate is not a field element")
+}
+
+fn fr(value: U256) -> Result<Fr> {
+    Fr::from_bigint(BigInt::new(value.into_limbs())).context("public input is not a field element")
+}
+
+/// Returns the G1 point with the given coordinates, where `(0, 0)` is the point at infinity as for
+/// the precompiles.
+fn g1([x, y]: [U256; 2]) -> Result<G1Affine> {
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1Affine::identity());
+    }
+    let point = G1Affine::new_unchecked(fq(x)?, fq(y)?);
+    ensure!(point.is_on_curve(), "G1 point is not on the curve");
+    Ok(point)
+}
+
+/// Returns the G2 point with coordinates encoded as `[imaginary, real]`.
+fn g2(x: [U256; 2], y: [U256; 2]) -> Result<G2Affine> {
+    if [x, y].iter().flatten().all(U256::is_zero) {
+        return Ok(G2Affine::identity());
+    }
+    let point = G2Affine::new_unchecked(
+        Fq2::new(fq(x[1])?, fq(x[0])?),
+        Fq2::new(fq(y[1])?, fq(y[0])?),
+    );
+    ensure!(
+        point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve(),
+        "G2 point is not in the subgroup"
+    );
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::b256;
+
+    use super::*;
+
+    // Known-good receipt of `TestReceipt.sol` in risc0-ethereum, verified by
+    // `RiscZeroGroth16Verifier.t.sol` with the same control IDs and verifying key.
+    const TEST_SEAL: [&str; 8] = [
+        "16542a20d91154d977fb6215f8366e48f9034afb2c71c20b6b3080dc30e98125",
+        "2e9fa9660f4a378686b14afb1e0092180bef679a07752939f832283929011eb0",
+        "06796cca253ca629c6d5b2852b30370427d19c32f5a0fa7b791f68db789b775b",
+        "20308dc1c19af9f2198e1d99c85be7771d63779bea244c7cf097bb90902601a6",
+        "01dc9c4464c797022372c3c3e9be96ff4cfec8a2a33161eae9e62c9d9175bbdc",
+        "27839ff9bfbd03554980bec8affc43b4dba3e06e9ce201d0269fa3fd82157d5b",
+        "091f9f0971190b276a4038f182855fc5f67ba806cdd2760d90d3eb4ef57e090f",
+        "02ca9ee4e7120f6ca7ce7f5b901dfc2aa1df72aaeb78c44353d449134c363efa",
+    ];
+    const TEST_IMAGE_ID: B256 =
+        b256!("790b91e4c2efa939aea3ee4989c82564d82047977970d014e53d95e44c3230db");
+    const TEST_POST_STATE_DIGEST: B256 =
+        b256!("41b150d6ac003f55763d57ee5512652b0446d6c7bd2789e8c0c1f4a5bb3e87d8");
+    const TEST_JOURNAL: &[u8] = b"";
+
+    fn test_seal() -> Vec<u8> {
+        hex::decode(TEST_SEAL.concat()).unwrap()
+    }
+
+    #[test]
+    fn known_good_seal() {
+        verify(
+            &test_seal(),
+            TEST_IMAGE_ID.0,
+            TEST_POST_STATE_DIGEST,
+            TEST_JOURNAL,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn known_good_seal_of_other_claim() {
+        let seal = test_seal();
+        let image_id = TEST_IMAGE_ID.0;
+        let post_state_digest = TEST_POST_STATE_DIGEST;
+
+        let err = verify(&seal, image_id, post_state_digest, b"journal").unwrap_err();
+        assert!(err.is::<InvalidSeal>());
+        let err = verify(&seal, Digest::ZERO, post_state_digest, TEST_JOURNAL).unwrap_err();
+        assert!(err.is::<InvalidSeal>());
+        let err = verify(&seal, image_id, B256::ZERO, TEST_JOURNAL).unwrap_err();
+        assert!(err.is::<InvalidSeal>());
+    }
+
+    #[test]
+    fn verifying_key_points_are_valid() {
+        let vk = verifying_key().unwrap();
+        assert_eq!(vk.gamma_abc_g1.len(), 6);
+    }
+
+    #[test]
+    fn split_digest_reverses_halves() {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 1;
+        bytes[31] = 2;
+        let (low, high) = split_digest(Digest::from(bytes));
+        assert_eq!(low, Fr::from(1u128));
+        assert_eq!(high, Fr::from(2u128 << 120));
+    }
+
+    #[test]
+    fn invalid_seal() {
+        let seal = Seal {
+            a: [U256::from(1), U256::from(2)],
+            b: [[U256::ZERO; 2]; 2],
+            c: [U256::from(1), U256::from(2)],
+        };
+        // The zero G2 point encodes the point at infinity, so the seal is well-formed.
+        let err = verify(&seal.abi_encode(), Digest::ZERO, B256::ZERO, b"journal").unwrap_err();
+        assert!(err.is::<InvalidSeal>());
+
+        let seal = Seal {
+            a: [U256::from(1), U256::from(3)],
+            ..seal
+        };
+        let err = verify(&seal.abi_encode(), Digest::ZERO, B256::ZERO, b"journal").unwrap_err();
+        assert!(!err.is::<InvalidSeal>());
+    }
+}
@@ -0,0 +1,494 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The Semaphore group of a `POAPGroup`, rebuilt from the `MemberAdded` events of Semaphore.
+
+use alloy_primitives::{keccak256, Address, B256, U256};
+use alloy_sol_types::{sol, SolCall, SolEvent};
+use anyhow::{ensure, Context, Result};
+use ethers::{
+    providers::{Http, JsonRpcClient, Middleware, MiddlewareError, Provider, ProviderError},
+    types::{Filter, Log, TransactionRequest, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zkpoap_types::poseidon;
+
+sol! {
+    /// The functions of `POAPGroup` used by members of the group.
+    interface IPOAPGroup {
+        struct Signal {
+            uint256 signal;
+            uint256 scope;
+            uint256 merkleTreeRoot;
+            uint256 nullifierHash;
+            uint256[8] proof;
+        }
+
+        function semaphore() external view returns (address);
+        function groupId() external view returns (uint256);
+        function blockHashOracle() external view returns (address);
+        function nullifires(bytes32 nullifier) external view returns (bool);
+        function validateSignal(Signal memory signal) external;
+    }
+
+    /// The oracle providing the block hashes of the POAP chain to a group on another chain.
+    interface IBlockHashOracle {
+        function blockHash(uint256 chainId, uint256 blockNumber) external view returns (bytes32);
+    }
+
+    /// The events of the Semaphore v3 groups.
+    interface ISemaphoreGroups {
+        event MemberAdded(uint256 indexed groupId, uint256 index, uint256 identityCommitment, uint256 merkleTreeRoot);
+    }
+
+    /// The events of Semaphore v3 signals.
+    interface ISemaphore {
+        event ProofVerified(uint256 indexed groupId, uint256 indexed merkleTreeRoot, uint256 nullifierHash, uint256 indexed externalNullifier, uint256 signal);
+    }
+}
+
+/// Depth of the Merkle trees of the groups created by `POAPGroup`.
+pub const TREE_DEPTH: usize = 20;
+
+/// Number of blocks queried for events at once, within the limits of common RPC providers.
+pub const LOG_RANGE: u64 = 10_000;
+
+/// A member added to the group, from its `MemberAdded` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    /// Index of the member in the Merkle tree.
+    pub index: u64,
+    pub commitment: U256,
+    /// Root of the Merkle tree after the member was added.
+    pub root: U256,
+    pub block_number: u64,
+    pub block_hash: B256,
+}
+
+impl Member {
+    /// Decodes a `MemberAdded` event.
+    pub fn decode(log: &Log) -> Result<Self> {
+        let topics = log.topics.iter().map(|topic| B256::from(topic.0));
+        let event = ISemaphoreGroups::MemberAdded::decode_raw_log(topics, &log.data, true)
+            .context("invalid MemberAdded event")?;
+        Ok(Self {
+            index: event.index.saturating_to(),
+            commitment: event.identityCommitment,
+            root: event.merkleTreeRoot,
+            block_number: log.block_number.context("event is pending")?.as_u64(),
+            block_hash: B256::from(log.block_hash.context("event is pending")?.0),
+        })
+    }
+}
+
+/// The Semaphore group of a `POAPGroup` deployment.
+#[derive(Debug, Clone)]
+pub struct SemaphoreGroup {
+    /// Address of the Semaphore contract holding the group.
+    pub semaphore: Address,
+    pub group_id: U256,
+}
+
+impl SemaphoreGroup {
+    /// Reads the Semaphore contract and the group ID of the `POAPGroup` at the given address.
+    pub async fn of(provider: &Provider<Http>, contract: Address) -> Result<Self> {
+        let semaphore = view(provider, contract, IPOAPGroup::semaphoreCall {})
+            .await?
+            ._0;
+        let group_id = view(provider, contract, IPOAPGroup::groupIdCall {})
+            .await?
+            ._0;
+        Ok(Self {
+            semaphore,
+            group_id,
+        })
+    }
+
+    /// Returns the value of the empty leaves of the group, as set by
+    /// `SemaphoreGroups._createGroup`.
+    pub fn zero_value(&self) -> U256 {
+        U256::from_be_bytes(keccak256(self.group_id.to_be_bytes::<32>()).0) >> 8
+    }
+
+    /// Returns the filter of the `MemberAdded` events of the group in the given blocks.
+    pub fn filter(&self, from_block: u64, to_block: u64) -> Filter {
+        Filter::new()
+            .address(H160::from_slice(self.semaphore.as_slice()))
+            .topic0(H256::from(ISemaphoreGroups::MemberAdded::SIGNATURE_HASH.0))
+            .topic1(H256::from(self.group_id.to_be_bytes::<32>()))
+            .from_block(from_block)
+            .to_block(to_block)
+    }
+
+    /// Returns the members added from the given block, e.g. the deployment block of `POAPGroup`,
+    /// ordered by index.
+    pub async fn members(&self, provider: &Provider<Http>, from_block: u64) -> Result<Vec<Member>> {
+        let latest = provider.get_block_number().await?.as_u64();
+        let filter = self.filter(from_block, latest);
+        let mut members = get_logs(provider, &filter, from_block, latest)
+            .await?
+            .iter()
+            .map(Member::decode)
+            .collect::<Result<Vec<_>>>()?;
+        members.sort_by_key(|member| member.index);
+        log::info!("Found {} members of group {}", members.len(), self.group_id);
+
+        Ok(members)
+    }
+
+    /// Rebuilds the Merkle tree of the group from all of its members, checking the root after
+    /// each member against the one of its event.
+    pub fn tree(&self, members: &[Member]) -> Result<MerkleTree> {
+        let mut tree = MerkleTree::new(self.zero_value());
+        for member in members {
+            tree.add_member(member)?;
+        }
+        Ok(tree)
+    }
+}
+
+/// Returns the logs matching the filter in the given blocks, queried [LOG_RANGE] blocks at a time.
+///
+/// As the log recovery of `risc0-ethereum-relay`, a range rejected by the provider is narrowed to
+/// the one suggested in the error, e.g. by Alchemy and Infura when there are too many logs, or
+/// halved otherwise. Suggestions that would not narrow the range are ignored, so that the queries
+/// always end.
+pub async fn get_logs<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Log>> {
+    let mut logs = Vec::new();
+    let mut from = from_block;
+    let mut range = LOG_RANGE;
+    while from <= to_block {
+        let to = (from + range - 1).min(to_block);
+        let filter = filter.clone().from_block(from).to_block(to);
+        match provider.get_logs(&filter).await {
+            Ok(batch) => {
+                logs.extend(batch);
+                from = to + 1;
+                range = (range * 2).min(LOG_RANGE);
+            }
+            Err(err) => {
+                range = match suggested_range(&err) {
+                    // Only a strictly smaller range from the same block narrows the query.
+                    Some((suggested_from, suggested_to))
+                        if suggested_from == from && (from..to).contains(&suggested_to) =>
+                    {
+                        suggested_to - from + 1
+                    }
+                    _ if to > from => (to - from + 1) / 2,
+                    _ => return Err(err).context("failed to get logs"),
+                };
+                log::debug!(
+                    "Narrowing log queries from block {} to {} blocks",
+                    from,
+                    range
+                );
+            }
+        }
+    }
+    Ok(logs)
+}
+
+/// Returns the block range suggested in a rejected log query, as `{"from": "0x..", "to": "0x.."}`
+/// in the data of the error.
+fn suggested_range(err: &ProviderError) -> Option<(u64, u64)> {
+    let data = err.as_error_response()?.data.as_ref()?;
+    let block = |key: &str| match data.get(key)? {
+        Value::String(number) => u64::from_str_radix(number.trim_start_matches("0x"), 16).ok(),
+        _ => None,
+    };
+    Some((block("from")?, block("to")?))
+}
+
+/// Returns whether a member already joined the `POAPGroup` at the given address with the nullifier.
+pub async fn is_nullifier_used(
+    provider: &Provider<Http>,
+    contract: Address,
+    nullifier: B256,
+) -> Result<bool> {
+    let call = IPOAPGroup::nullifiresCall { nullifier };
+    Ok(view(provider, contract, call).await?._0)
+}
+
+/// Returns the hash of a block of the POAP chain as provided by the block hash oracle of the group,
+/// or `None` if the group has no oracle or the oracle does not know the block.
+pub async fn oracle_block_hash(
+    provider: &Provider<Http>,
+    contract: Address,
+    poap_chain_id: u64,
+    block_number: U256,
+) -> Result<Option<B256>> {
+    let oracle = view(provider, contract, IPOAPGroup::blockHashOracleCall {})
+        .await?
+        ._0;
+    if oracle == Address::ZERO {
+        return Ok(None);
+    }
+    let call = IBlockHashOracle::blockHashCall {
+        chainId: U256::from(poap_chain_id),
+        blockNumber: block_number,
+    };
+    let block_hash = view(provider, oracle, call).await?._0;
+    Ok((block_hash != B256::ZERO).then_some(block_hash))
+}
+
+/// Calls a view function of the contract at the latest block.
+async fn view<C: SolCall>(
+    provider: &Provider<Http>,
+    contract: Address,
+    call: C,
+) -> Result<C::Return> {
+    let tx = TransactionRequest::new()
+        .to(H160::from_slice(contract.as_slice()))
+        .data(call.abi_encode());
+    let returns = provider.call(&tx.into(), None).await?;
+    C::abi_decode_returns(&returns, true)
+        .with_context(|| format!("invalid return data of {}", C::SIGNATURE))
+}
+
+/// An incremental binary Merkle tree with Poseidon hashes, as `IncrementalBinaryTree` of Semaphore.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    /// Roots of empty subtrees, by level.
+    zeroes: Vec<U256>,
+    /// Non-empty nodes, by level from the leaves to the root.
+    levels: Vec<Vec<U256>>,
+}
+
+impl MerkleTree {
+    /// Creates an empty tree of depth [TREE_DEPTH] with the given value of empty leaves.
+    pub fn new(zero_value: U256) -> Self {
+        let mut zeroes = vec![zero_value];
+        for level in 0..TREE_DEPTH {
+            zeroes.push(poseidon(&[zeroes[level], zeroes[level]]));
+        }
+        Self {
+            zeroes,
+            levels: vec![Vec::new(); TREE_DEPTH + 1],
+        }
+    }
+
+    /// Returns the number of leaves.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn root(&self) -> U256 {
+        self.node(TREE_DEPTH, 0)
+    }
+
+    /// Returns the index of the given leaf.
+    pub fn index_of(&self, leaf: U256) -> Option<usize> {
+        self.levels[0].iter().position(|node| *node == leaf)
+    }
+
+    /// Appends a leaf, updating the nodes on its path to the root.
+    pub fn insert(&mut self, leaf: U256) -> Result<()> {
+        ensure!(self.len() < 1 << TREE_DEPTH, "the Merkle tree is full");
+
+        let mut index = self.len();
+        let mut node = leaf;
+        for level in 0..TREE_DEPTH {
+            set(&mut self.levels[level], index, node);
+            node = match index % 2 {
+                0 => poseidon(&[node, self.zeroes[level]]),
+                _ => poseidon(&[self.levels[level][index - 1], node]),
+            };
+            index /= 2;
+        }
+        set(&mut self.levels[TREE_DEPTH], index, node);
+
+        Ok(())
+    }
+
+    /// Appends the member, which must be the next leaf, checking the root against the one of its
+    /// event.
+    pub fn add_member(&mut self, member: &Member) -> Result<()> {
+        ensure!(
+            member.index == self.len() as u64,
+            "missing member {} of the group; search events from an earlier block",
+            self.len()
+        );
+        self.insert(member.commitment)?;
+        ensure!(
+            self.root() == member.root,
+            "rebuilt Merkle tree does not match the root of member {}",
+            member.index
+        );
+        Ok(())
+    }
+
+    /// Returns the inclusion proof of the leaf at the given index.
+    pub fn proof(&self, index: usize) -> Result<MerkleProof> {
+        ensure!(index < self.len(), "no leaf at index {}", index);
+
+        let mut siblings = Vec::with_capacity(TREE_DEPTH);
+        let mut path_indices = Vec::with_capacity(TREE_DEPTH);
+        for level in 0..TREE_DEPTH {
+            let position = index >> level;
+            siblings.push(self.node(level, position ^ 1));
+            path_indices.push((position % 2) as u8);
+        }
+        Ok(MerkleProof {
+            root: self.root(),
+            leaf: self.levels[0][index],
+            siblings,
+            path_indices,
+        })
+    }
+
+    /// Returns the node at the given level and position, which is the root of an empty subtree if
+    /// the position is not filled.
+    fn node(&self, level: usize, position: usize) -> U256 {
+        self.levels[level]
+            .get(position)
+            .copied()
+            .unwrap_or(self.zeroes[level])
+    }
+}
+
+fn set(nodes: &mut Vec<U256>, position: usize, node: U256) {
+    if position < nodes.len() {
+        nodes[position] = node;
+    } else {
+        nodes.push(node);
+    }
+}
+
+/// The inclusion proof of a leaf, with the siblings and the path from the leaf to the root, where
+/// 1 means that the node is the right child.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub root: U256,
+    pub leaf: U256,
+    pub siblings: Vec<U256>,
+    pub path_indices: Vec<u8>,
+}
+
+impl MerkleProof {
+    /// Checks that the path from the leaf leads to the root.
+    pub fn verify(&self) -> bool {
+        let node = self.siblings.iter().zip(&self.path_indices).fold(
+            self.leaf,
+            |node, (sibling, index)| match index {
+                0 => poseidon(&[node, *sibling]),
+                _ => poseidon(&[*sibling, node]),
+            },
+        );
+        node == self.root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::providers::{HttpClientError, JsonRpcError, MockResponse};
+
+    use super::*;
+
+    #[test]
+    fn zero_value() {
+        let group = SemaphoreGroup {
+            semaphore: Address::ZERO,
+            group_id: U256::ZERO,
+        };
+        // keccak256 of 32 zero bytes, shifted to fit the field.
+        let expected = "0x00290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e5";
+        assert_eq!(group.zero_value(), expected.parse::<U256>().unwrap());
+    }
+
+    #[test]
+    fn suggested_log_range() {
+        let err = JsonRpcError {
+            code: -32602,
+            message: "query returned more than 10000 results".into(),
+            data: Some(serde_json::json!({ "from": "0x10", "to": "0x1f" })),
+        };
+        let err = ProviderError::JsonRpcClientError(Box::new(HttpClientError::JsonRpcError(err)));
+        assert_eq!(suggested_range(&err), Some((16, 31)));
+
+        let err = ProviderError::CustomError("timeout".into());
+        assert_eq!(suggested_range(&err), None);
+    }
+
+    fn too_many_results(from: u64, to: u64) -> MockResponse {
+        MockResponse::Error(JsonRpcError {
+            code: -32602,
+            message: "query returned more than 10000 results".into(),
+            data: Some(serde_json::json!({
+                "from": format!("{:#x}", from),
+                "to": format!("{:#x}", to),
+            })),
+        })
+    }
+
+    #[tokio::test]
+    async fn get_logs_narrows_rejected_ranges() {
+        let (provider, mock) = Provider::mocked();
+        // Responses are popped from the back.
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        mock.push_response(too_many_results(100, 199));
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        mock.push_response(too_many_results(0, 99));
+
+        let filter = Filter::new();
+        get_logs(&provider, &filter, 0, 199).await.unwrap();
+        // The suggestion of the rejected range itself is ignored, and the range halved instead.
+        for (from, to) in [(0u64, 199u64), (0, 99), (100, 199), (100, 149), (150, 199)] {
+            mock.assert_request(
+                "eth_getLogs",
+                [filter.clone().from_block(from).to_block(to)],
+            )
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn get_logs_fails_on_rejected_block() {
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(too_many_results(5, 5));
+        assert!(get_logs(&provider, &Filter::new(), 5, 5).await.is_err());
+    }
+
+    #[test]
+    fn inclusion_proofs() {
+        let mut tree = MerkleTree::new(U256::from(42));
+        let empty_root = tree.root();
+        for leaf in 1..=5u64 {
+            tree.insert(U256::from(leaf)).unwrap();
+        }
+        assert_ne!(tree.root(), empty_root);
+        assert_eq!(tree.index_of(U256::from(3)), Some(2));
+
+        for index in 0..tree.len() {
+            let proof = tree.proof(index).unwrap();
+            assert_eq!(proof.root, tree.root());
+            assert!(proof.verify());
+        }
+        assert!(tree.proof(5).is_err());
+
+        let mut proof = tree.proof(1).unwrap();
+        proof.leaf = U256::from(6);
+        assert!(!proof.verify());
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local execution of the guests, to detect the inputs they reject before proving.
+
+use anyhow::{bail, Context, Result};
+use risc0_zkvm::{default_executor, ExecutorEnv, ExitCode, Receipt, SessionInfo};
+use zkpoap_types::{GuestError, ERROR_EXIT_CODE};
+
+/// Executes the guest locally with the given input.
+///
+/// Returns the [GuestError] if the guest rejected its input, so that no proof is requested for an
+/// input that cannot be verified.
+pub fn execute(elf: &[u8], input: &[u8]) -> Result<SessionInfo> {
+    execute_with_assumptions(elf, input, &[])
+}
+
+/// Executes a guest calling `env::verify` locally, resolving each call with one of the given
+/// receipts.
+pub fn execute_with_assumptions(
+    elf: &[u8],
+    input: &[u8],
+    assumptions: &[Receipt],
+) -> Result<SessionInfo> {
+    let mut builder = ExecutorEnv::builder();
+    for receipt in assumptions {
+        builder.add_assumption(receipt.clone());
+    }
+    let env = builder
+        .write_slice(input)
+        .build()
+        .context("failed to build exec env")?;
+    let session_info = default_executor()
+        .execute(env, elf)
+        .context("failed to run executor")?;
+
+    match session_info.exit_code {
+        ExitCode::Halted(0) => Ok(session_info),
+        ExitCode::Halted(code) if code == u32::from(ERROR_EXIT_CODE) => {
+            let err: GuestError = session_info
+                .journal
+                .decode()
+                .context("failed to decode guest error")?;
+            Err(err.into())
+        }
+        exit_code => bail!("guest exited with {:?}", exit_code),
+    }
+}
@@ -0,0 +1,747 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Indexer of the members of a `POAPGroup` and of the nullifiers they used, following the events
+//! of Semaphore, so that clients can build Merkle proofs and check nullifiers before proving.
+//!
+//! The index is persisted as JSON, and served with [app] as JSON under [GROUP_ROUTE],
+//! [MEMBERS_ROUTE], [MEMBER_PROOF_ROUTE], [NULLIFIERS_ROUTE], [NULLIFIER_ROUTE] and
+//! [SIGNALS_ROUTE].
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use alloy_primitives::{Address, B256, U256};
+use alloy_sol_types::SolEvent;
+use anyhow::{bail, ensure, Context, Result};
+use axum::{extract, http::StatusCode, routing::get, Json, Router};
+use ethers::{
+    providers::{Http, JsonRpcClient, Middleware, Provider},
+    types::{BlockNumber, Filter, Log, H160, H256},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    group::{
+        get_logs, ISemaphore, ISemaphoreGroups, Member, MerkleProof, MerkleTree, SemaphoreGroup,
+    },
+    submission::Submission,
+};
+
+/// Number of synced blocks kept to find the last block still on the chain after a reorg.
+pub const CHECKPOINTS: usize = 64;
+
+/// Number of times a sync is retried when the chain is reorganized during it.
+pub const SYNC_ATTEMPTS: usize = 3;
+
+/// A nullifier used to join the group, from the journal of the proof.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MembershipNullifier {
+    pub nullifier: B256,
+    /// Identity commitment of the member added with the nullifier.
+    pub commitment: U256,
+    pub block_number: u64,
+}
+
+/// The nullifier hash of a signal validated by Semaphore, from its `ProofVerified` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignalNullifier {
+    pub scope: U256,
+    pub nullifier_hash: U256,
+    pub signal: U256,
+    pub block_number: u64,
+}
+
+/// A synced block, to detect reorgs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub number: u64,
+    pub hash: B256,
+}
+
+/// The indexed state of a group, persisted as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupIndex {
+    /// Address of the `POAPGroup`.
+    pub contract: Address,
+    pub semaphore: Address,
+    pub group_id: U256,
+    /// Block the events are indexed from.
+    pub from_block: u64,
+    /// The last [CHECKPOINTS] synced blocks, oldest first.
+    pub checkpoints: Vec<Checkpoint>,
+    /// Members ordered by index.
+    pub members: Vec<Member>,
+    pub nullifiers: Vec<MembershipNullifier>,
+    pub signals: Vec<SignalNullifier>,
+}
+
+impl GroupIndex {
+    /// Loads the index from a JSON file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open index file {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid index file {}", path.display()))
+    }
+
+    /// Saves the index to a JSON file, replacing it atomically.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)
+            .with_context(|| format!("failed to write index file {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("failed to write index file {}", path.display()))
+    }
+
+    pub fn group(&self) -> SemaphoreGroup {
+        SemaphoreGroup {
+            semaphore: self.semaphore,
+            group_id: self.group_id,
+        }
+    }
+
+    /// Returns the first block that is not indexed yet.
+    pub fn next_block(&self) -> u64 {
+        self.checkpoints
+            .last()
+            .map_or(self.from_block, |checkpoint| checkpoint.number + 1)
+    }
+
+    /// Drops the events of the blocks from the given one, e.g. after a reorg.
+    fn rollback(&mut self, block_number: u64) {
+        self.members
+            .retain(|member| member.block_number < block_number);
+        self.nullifiers
+            .retain(|nullifier| nullifier.block_number < block_number);
+        self.signals
+            .retain(|signal| signal.block_number < block_number);
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.number < block_number);
+    }
+}
+
+/// Follows a group, keeping its index and Merkle tree up to date.
+#[derive(Debug, Clone)]
+pub struct GroupIndexer {
+    index: GroupIndex,
+    tree: MerkleTree,
+    /// File the index is saved to after each sync.
+    file: Option<PathBuf>,
+}
+
+impl GroupIndexer {
+    /// Creates an indexer of the `POAPGroup` at the given address, indexing its events from the
+    /// given block, e.g. its deployment block.
+    pub async fn new(
+        provider: &Provider<Http>,
+        contract: Address,
+        from_block: u64,
+    ) -> Result<Self> {
+        let group = SemaphoreGroup::of(provider, contract).await?;
+        Self::from_index(GroupIndex {
+            contract,
+            semaphore: group.semaphore,
+            group_id: group.group_id,
+            from_block,
+            checkpoints: Vec::new(),
+            members: Vec::new(),
+            nullifiers: Vec::new(),
+            signals: Vec::new(),
+        })
+    }
+
+    /// Resumes from the index saved in the file, or creates a new indexer if the file does not
+    /// exist. The index is saved to the file after each sync.
+    pub async fn open(
+        provider: &Provider<Http>,
+        contract: Address,
+        from_block: u64,
+        path: PathBuf,
+    ) -> Result<Self> {
+        let mut indexer = if path.exists() {
+            let index = GroupIndex::load(&path)?;
+            ensure!(
+                index.contract == contract,
+                "index file {} is of the group {}",
+                path.display(),
+                index.contract
+            );
+            Self::from_index(index)?
+        } else {
+            Self::new(provider, contract, from_block).await?
+        };
+        indexer.file = Some(path);
+
+        Ok(indexer)
+    }
+
+    /// Creates an indexer from a saved index, rebuilding its Merkle tree.
+    pub fn from_index(index: GroupIndex) -> Result<Self> {
+        let tree = index.group().tree(&index.members)?;
+        Ok(Self {
+            index,
+            tree,
+            file: None,
+        })
+    }
+
+    pub fn index(&self) -> &GroupIndex {
+        &self.index
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn members(&self) -> &[Member] {
+        &self.index.members
+    }
+
+    /// Returns the inclusion proof of the member with the given identity commitment.
+    pub fn proof(&self, commitment: U256) -> Option<MerkleProof> {
+        let index = self.tree.index_of(commitment)?;
+        self.tree.proof(index).ok()
+    }
+
+    /// Returns whether the nullifier was used to join the group, so that a proof with it would be
+    /// rejected as `DUPLICATE_PROOF`.
+    pub fn is_nullifier_used(&self, nullifier: B256) -> bool {
+        self.index
+            .nullifiers
+            .iter()
+            .any(|used| used.nullifier == nullifier)
+    }
+
+    /// Returns whether a signal with the nullifier hash was validated, so that another signal of
+    /// the member in the same scope would be rejected.
+    pub fn is_signal_nullifier_used(&self, nullifier_hash: U256) -> bool {
+        self.index
+            .signals
+            .iter()
+            .any(|used| used.nullifier_hash == nullifier_hash)
+    }
+
+    /// Indexes the events up to the latest block, after dropping the blocks removed by a reorg.
+    ///
+    /// Events of blocks that are no longer on the chain, i.e. of a reorg during the sync, are
+    /// never indexed; the sync is retried instead.
+    pub async fn sync<P: JsonRpcClient>(&mut self, provider: &Provider<P>) -> Result<()> {
+        for _ in 0..SYNC_ATTEMPTS {
+            self.handle_reorg(provider).await?;
+            if self.sync_to_latest(provider).await? {
+                if let Some(path) = &self.file {
+                    self.index.save(path)?;
+                }
+                return Ok(());
+            }
+            log::warn!("The chain was reorganized during the sync, syncing again");
+        }
+        bail!(
+            "the chain was reorganized during {} syncs in a row",
+            SYNC_ATTEMPTS
+        )
+    }
+
+    /// Indexes the events from the next block to the latest one. Returns `false`, leaving the index
+    /// unchanged, if an event is of a block that is no longer on the chain.
+    async fn sync_to_latest<P: JsonRpcClient>(&mut self, provider: &Provider<P>) -> Result<bool> {
+        let latest = provider
+            .get_block(BlockNumber::Latest)
+            .await?
+            .context("latest block not found")?;
+        let checkpoint = Checkpoint {
+            number: latest.number.context("latest block is pending")?.as_u64(),
+            hash: B256::from(latest.hash.context("latest block is pending")?.0),
+        };
+        let from = self.index.next_block();
+        if from > checkpoint.number {
+            return Ok(true);
+        }
+
+        let group = self.index.group();
+        let filter = Filter::new()
+            .address(H160::from_slice(group.semaphore.as_slice()))
+            .topic0(vec![
+                H256::from(ISemaphoreGroups::MemberAdded::SIGNATURE_HASH.0),
+                H256::from(ISemaphore::ProofVerified::SIGNATURE_HASH.0),
+            ])
+            .topic1(H256::from(group.group_id.to_be_bytes::<32>()));
+        let logs = get_logs(provider, &filter, from, checkpoint.number).await?;
+        if !is_canonical(provider, &logs, checkpoint).await? {
+            return Ok(false);
+        }
+
+        // The index is only updated once all events are processed.
+        let mut tree = self.tree.clone();
+        let mut members = Vec::new();
+        let mut signals = Vec::new();
+        let mut transactions = Vec::new();
+        for log in &logs {
+            let topic = log.topics.first().map(|topic| topic.0);
+            if topic == Some(ISemaphoreGroups::MemberAdded::SIGNATURE_HASH.0) {
+                let member = Member::decode(log)?;
+                tree.add_member(&member)?;
+                // Batches add several members in the same transaction.
+                if transactions.last() != Some(&(log.transaction_hash, member.block_number)) {
+                    transactions.push((log.transaction_hash, member.block_number));
+                }
+                members.push(member);
+            } else if topic == Some(ISemaphore::ProofVerified::SIGNATURE_HASH.0) {
+                signals.push(decode_signal(log)?);
+            }
+        }
+
+        let mut nullifiers: Vec<MembershipNullifier> = Vec::new();
+        for (hash, block_number) in transactions {
+            let hash = hash.context("event without transaction")?;
+            // `joinGroupBatch` skips the entries with used nullifiers, which add no member.
+            for membership in self
+                .membership_nullifiers(provider, hash, block_number)
+                .await?
+            {
+                let nullifier = membership.nullifier;
+                if !self.is_nullifier_used(nullifier)
+                    && !nullifiers.iter().any(|used| used.nullifier == nullifier)
+                {
+                    nullifiers.push(membership);
+                }
+            }
+        }
+
+        log::info!(
+            "Indexed blocks {} to {}: {} members, {} signals",
+            from,
+            checkpoint.number,
+            members.len(),
+            signals.len()
+        );
+        self.tree = tree;
+        self.index.members.extend(members);
+        self.index.nullifiers.extend(nullifiers);
+        self.index.signals.extend(signals);
+        self.index.checkpoints.push(checkpoint);
+        let excess = self.index.checkpoints.len().saturating_sub(CHECKPOINTS);
+        self.index.checkpoints.drain(..excess);
+
+        Ok(true)
+    }
+
+    /// Drops the synced blocks that are no longer on the chain, back to the last checkpoint that
+    /// is, or to the first block if there is none.
+    async fn handle_reorg<P: JsonRpcClient>(&mut self, provider: &Provider<P>) -> Result<()> {
+        let mut reorged = false;
+        while let Some(checkpoint) = self.index.checkpoints.last().copied() {
+            let hash = provider
+                .get_block(checkpoint.number)
+                .await?
+                .and_then(|block| block.hash);
+            if hash.map(|hash| B256::from(hash.0)) == Some(checkpoint.hash) {
+                break;
+            }
+            log::warn!("Block {} was removed by a reorg", checkpoint.number);
+            self.index.checkpoints.pop();
+            reorged = true;
+        }
+
+        if reorged {
+            let next_block = self.index.next_block();
+            self.index.rollback(next_block);
+            self.tree = self.index.group().tree(&self.index.members)?;
+            log::warn!("Rolled back the index to block {}", next_block);
+        }
+        Ok(())
+    }
+
+    /// Returns the nullifiers of the members added by the transaction, from the journal of its
+    /// proof.
+    async fn membership_nullifiers<P: JsonRpcClient>(
+        &self,
+        provider: &Provider<P>,
+        hash: H256,
+        block_number: u64,
+    ) -> Result<Vec<MembershipNullifier>> {
+        let tx = provider
+            .get_transaction(hash)
+            .await?
+            .with_context(|| format!("transaction {:?} not found", hash))?;
+        // Proofs submitted through another contract cannot be decoded from the calldata.
+        if tx.to != Some(H160::from_slice(self.index.contract.as_slice())) {
+            log::warn!(
+                "Members added through another contract by {:?}; nullifiers not indexed",
+                hash
+            );
+            return Ok(Vec::new());
+        }
+
+        let submission = Submission::from_calldata(&tx.input)?;
+        Ok(submission
+            .proof_data()?
+            .into_iter()
+            .map(|proof_data| MembershipNullifier {
+                nullifier: proof_data.nullifier,
+                commitment: proof_data.semaphoreId,
+                block_number,
+            })
+            .collect())
+    }
+}
+
+/// Returns whether the logs are of the blocks of the chain ending at the latest block, i.e.
+/// whether the block hash of each log is the one of its block now.
+async fn is_canonical<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    logs: &[Log],
+    latest: Checkpoint,
+) -> Result<bool> {
+    let mut blocks = BTreeMap::new();
+    for log in logs {
+        let number = log.block_number.context("event is pending")?.as_u64();
+        let hash = B256::from(log.block_hash.context("event is pending")?.0);
+        if *blocks.entry(number).or_insert(hash) != hash {
+            return Ok(false);
+        }
+    }
+
+    for (number, hash) in blocks {
+        let canonical = match number == latest.number {
+            true => Some(latest.hash),
+            false => provider
+                .get_block(number)
+                .await?
+                .and_then(|block| block.hash)
+                .map(|hash| B256::from(hash.0)),
+        };
+        if canonical != Some(hash) {
+            log::warn!("Block {} of the events is no longer on the chain", number);
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Decodes a `ProofVerified` event.
+fn decode_signal(log: &Log) -> Result<SignalNullifier> {
+    let topics = log.topics.iter().map(|topic| B256::from(topic.0));
+    let event = ISemaphore::ProofVerified::decode_raw_log(topics, &log.data, true)
+        .context("invalid ProofVerified event")?;
+    Ok(SignalNullifier {
+        scope: event.externalNullifier,
+        nullifier_hash: event.nullifierHash,
+        signal: event.signal,
+        block_number: log.block_number.context("event is pending")?.as_u64(),
+    })
+}
+
+/// An indexer shared by the HTTP API and [follow].
+pub type SharedIndexer = Arc<RwLock<GroupIndexer>>;
+
+/// Syncs the indexer every `interval`. Failed syncs are logged and retried at the next one.
+pub async fn follow(indexer: SharedIndexer, provider: Provider<Http>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        // Sync a copy, so that the index keeps being served meanwhile.
+        let mut next = indexer.read().await.clone();
+        match next.sync(&provider).await {
+            Ok(()) => *indexer.write().await = next,
+            Err(err) => log::error!("Failed to sync the group: {:#}", err),
+        }
+    }
+}
+
+/// Route of the [GroupInfo].
+pub const GROUP_ROUTE: &str = "/v1/group";
+/// Route of the members, ordered by index.
+pub const MEMBERS_ROUTE: &str = "/v1/members";
+/// Route of the [MerkleProof] of a member, by identity commitment.
+pub const MEMBER_PROOF_ROUTE: &str = "/v1/members/:commitment/proof";
+/// Route of the nullifiers used to join the group.
+pub const NULLIFIERS_ROUTE: &str = "/v1/nullifiers";
+/// Route of the [NullifierStatus] of a nullifier.
+pub const NULLIFIER_ROUTE: &str = "/v1/nullifiers/:nullifier";
+/// Route of the nullifier hashes of the validated signals.
+pub const SIGNALS_ROUTE: &str = "/v1/signals";
+
+/// Summary of the group returned by [GROUP_ROUTE].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupInfo {
+    pub contract: Address,
+    pub group_id: U256,
+    pub root: U256,
+    pub size: usize,
+    /// Last synced block.
+    pub block_number: Option<u64>,
+}
+
+/// Whether a nullifier was used to join the group, returned by [NULLIFIER_ROUTE].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NullifierStatus {
+    pub used: bool,
+}
+
+/// Returns the router of the indexer API.
+pub fn app(indexer: SharedIndexer) -> Router {
+    Router::new()
+        .route(GROUP_ROUTE, get(get_group))
+        .route(MEMBERS_ROUTE, get(get_members))
+        .route(MEMBER_PROOF_ROUTE, get(get_member_proof))
+        .route(NULLIFIERS_ROUTE, get(get_nullifiers))
+        .route(NULLIFIER_ROUTE, get(get_nullifier))
+        .route(SIGNALS_ROUTE, get(get_signals))
+        .with_state(indexer)
+}
+
+/// Serves the indexer API on the given port.
+pub async fn serve(indexer: SharedIndexer, port: u16) -> Result<()> {
+    let bind_address = format!("0.0.0.0:{port}");
+    let listener = tokio::net::TcpListener::bind(&bind_address)
+        .await
+        .with_context(|| format!("failed to bind {bind_address}"))?;
+    log::info!("Indexer listening on {}", bind_address);
+    axum::serve(listener, app(indexer))
+        .await
+        .with_context(|| format!("failed to serve API on {bind_address}"))
+}
+
+type State = extract::State<SharedIndexer>;
+
+async fn get_group(extract::State(indexer): State) -> Json<GroupInfo> {
+    let indexer = indexer.read().await;
+    Json(GroupInfo {
+        contract: indexer.index.contract,
+        group_id: indexer.index.group_id,
+        root: indexer.tree.root(),
+        size: indexer.tree.len(),
+        block_number: indexer
+            .index
+            .checkpoints
+            .last()
+            .map(|checkpoint| checkpoint.number),
+    })
+}
+
+async fn get_members(extract::State(indexer): State) -> Json<Vec<Member>> {
+    Json(indexer.read().await.index.members.clone())
+}
+
+async fn get_member_proof(
+    extract::State(indexer): State,
+    extract::Path(commitment): extract::Path<U256>,
+) -> Result<Json<MerkleProof>, StatusCode> {
+    let proof = indexer.read().await.proof(commitment);
+    proof.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn get_nullifiers(extract::State(indexer): State) -> Json<Vec<MembershipNullifier>> {
+    Json(indexer.read().await.index.nullifiers.clone())
+}
+
+async fn get_nullifier(
+    extract::State(indexer): State,
+    extract::Path(nullifier): extract::Path<B256>,
+) -> Json<NullifierStatus> {
+    let used = indexer.read().await.is_nullifier_used(nullifier);
+    Json(NullifierStatus { used })
+}
+
+async fn get_signals(extract::State(indexer): State) -> Json<Vec<SignalNullifier>> {
+    Json(indexer.read().await.index.signals.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Block, Transaction};
+
+    use super::*;
+
+    fn group() -> SemaphoreGroup {
+        SemaphoreGroup {
+            semaphore: Address::repeat_byte(2),
+            group_id: U256::from(1),
+        }
+    }
+
+    fn checkpoint(number: u64, hash: u8) -> Checkpoint {
+        Checkpoint {
+            number,
+            hash: B256::repeat_byte(hash),
+        }
+    }
+
+    fn block(checkpoint: Checkpoint) -> Block<H256> {
+        Block {
+            number: Some(checkpoint.number.into()),
+            hash: Some(H256::from(checkpoint.hash.0)),
+            ..Default::default()
+        }
+    }
+
+    /// Returns members added in the given blocks, with the roots of the tree of the group.
+    fn added_members(blocks: &[u64]) -> Vec<Member> {
+        let mut tree = MerkleTree::new(group().zero_value());
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block_number)| {
+                let commitment = U256::from(index + 1);
+                tree.insert(commitment).unwrap();
+                Member {
+                    index: index as u64,
+                    commitment,
+                    root: tree.root(),
+                    block_number: *block_number,
+                    block_hash: B256::repeat_byte(*block_number as u8),
+                }
+            })
+            .collect()
+    }
+
+    fn member_added_log(member: &Member) -> Log {
+        let event = ISemaphoreGroups::MemberAdded {
+            groupId: group().group_id,
+            index: U256::from(member.index),
+            identityCommitment: member.commitment,
+            merkleTreeRoot: member.root,
+        };
+        Log {
+            address: H160::from_slice(group().semaphore.as_slice()),
+            topics: vec![
+                H256::from(ISemaphoreGroups::MemberAdded::SIGNATURE_HASH.0),
+                H256::from(group().group_id.to_be_bytes::<32>()),
+            ],
+            data: event.encode_data().into(),
+            block_number: Some(member.block_number.into()),
+            block_hash: Some(H256::from(member.block_hash.0)),
+            transaction_hash: Some(H256::repeat_byte(member.index as u8 + 1)),
+            ..Default::default()
+        }
+    }
+
+    fn indexer(checkpoints: Vec<Checkpoint>, members: Vec<Member>) -> GroupIndexer {
+        GroupIndexer::from_index(GroupIndex {
+            contract: Address::repeat_byte(1),
+            semaphore: group().semaphore,
+            group_id: group().group_id,
+            from_block: 10,
+            checkpoints,
+            members,
+            nullifiers: Vec::new(),
+            signals: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sync_rolls_back_reorged_blocks() {
+        let members = added_members(&[11, 14, 15]);
+        let mut indexer = indexer(
+            vec![checkpoint(12, 12), checkpoint(15, 15)],
+            members.clone(),
+        );
+
+        let (provider, mock) = Provider::mocked();
+        // Responses are popped from the back: block 15 was replaced, block 12 was not, and there
+        // are no events in the new blocks.
+        mock.push::<Vec<Log>, _>(Vec::new()).unwrap();
+        mock.push(block(checkpoint(16, 16))).unwrap();
+        mock.push(block(checkpoint(12, 12))).unwrap();
+        mock.push(block(checkpoint(15, 0xff))).unwrap();
+        indexer.sync(&provider).await.unwrap();
+
+        assert_eq!(indexer.members(), &members[..1]);
+        assert_eq!(indexer.tree().len(), 1);
+        assert_eq!(
+            indexer.index().checkpoints,
+            vec![checkpoint(12, 12), checkpoint(16, 16)]
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_retries_events_of_reorged_blocks() {
+        let member = added_members(&[12]).remove(0);
+        let mut stale = member.clone();
+        stale.block_hash = B256::repeat_byte(0xff);
+        let mut indexer = indexer(Vec::new(), Vec::new());
+
+        let (provider, mock) = Provider::mocked();
+        // Responses are popped from the back. The first events are of a block that was replaced
+        // by the time its hash is checked, so they are queried again.
+        mock.push(Transaction::default()).unwrap();
+        mock.push(block(checkpoint(12, 12))).unwrap();
+        mock.push::<Vec<Log>, _>(vec![member_added_log(&member)])
+            .unwrap();
+        mock.push(block(checkpoint(14, 14))).unwrap();
+        mock.push(block(checkpoint(12, 12))).unwrap();
+        mock.push::<Vec<Log>, _>(vec![member_added_log(&stale)])
+            .unwrap();
+        mock.push(block(checkpoint(14, 14))).unwrap();
+        indexer.sync(&provider).await.unwrap();
+
+        assert_eq!(indexer.members(), &[member]);
+        assert_eq!(indexer.index().checkpoints, vec![checkpoint(14, 14)]);
+        // The transaction is not to the group contract, so no nullifier is decoded.
+        assert!(indexer.index().nullifiers.is_empty());
+    }
+
+    fn member(index: u64, block_number: u64) -> Member {
+        Member {
+            index,
+            commitment: U256::from(index + 1),
+            root: U256::ZERO,
+            block_number,
+            block_hash: B256::ZERO,
+        }
+    }
+
+    #[test]
+    fn rollback() {
+        let mut index = GroupIndex {
+            contract: Address::ZERO,
+            semaphore: Address::ZERO,
+            group_id: U256::ZERO,
+            from_block: 10,
+            checkpoints: vec![
+                Checkpoint {
+                    number: 12,
+                    hash: B256::repeat_byte(1),
+                },
+                Checkpoint {
+                    number: 15,
+                    hash: B256::repeat_byte(2),
+                },
+            ],
+            members: vec![member(0, 11), member(1, 14), member(2, 15)],
+            nullifiers: Vec::new(),
+            signals: Vec::new(),
+        };
+        assert_eq!(index.next_block(), 16);
+
+        index.checkpoints.pop();
+        index.rollback(index.next_block());
+        assert_eq!(index.next_block(), 13);
+        assert_eq!(index.members, vec![member(0, 11)]);
+
+        index.checkpoints.clear();
+        index.rollback(index.next_block());
+        assert_eq!(index.next_block(), 10);
+        assert!(index.members.is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decoding of the journals committed by the guests, and validation of their block commitment.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use alloy_primitives::{Address, B256};
+use alloy_sol_types::SolValue;
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use ethers::providers::{Http, Middleware, Provider};
+use zkpoap_types::{BlockCommitment, ProofData, SetProofData};
+
+use crate::group::oracle_block_hash;
+
+/// Number of past blocks whose hash is available to the `BLOCKHASH` opcode.
+pub const BLOCKHASH_WINDOW: u64 = 256;
+
+/// Maximum age of a block whose hash cannot be checked, as `POAPGroup.MAX_BLOCK_AGE`.
+pub const MAX_BLOCK_AGE: Duration = Duration::from_secs(3600);
+
+/// The decoded journal of the `is_poap_owner` guest.
+#[derive(Debug)]
+pub struct Journal {
+    pub proof_data: ProofData,
+    /// The block the POAP ownership was proven at.
+    pub block: BlockCommitment,
+    /// The block since which the POAP has been held.
+    pub held_since: BlockCommitment,
+}
+
+impl Journal {
+    /// Decodes the ABI encoded journal, as `POAPGroup.joinGroup` does.
+    pub fn decode(journal: &[u8]) -> Result<Self> {
+        let (proof_data, block, held_since) =
+            <(ProofData, BlockCommitment, BlockCommitment)>::abi_decode(journal, true)
+                .context("invalid journal")?;
+        Ok(Self {
+            proof_data,
+            block,
+            held_since,
+        })
+    }
+}
+
+/// The decoded journal of the `compose_poap_owner` guest.
+#[derive(Debug)]
+pub struct ComposedJournal {
+    /// The journal in the format of `is_poap_owner`, without a holding age.
+    pub journal: Journal,
+    /// Image ID of the `poap_ownership` guest whose receipt was composed.
+    pub ownership_image_id: B256,
+    /// Image ID of the `owner_signature` guest whose receipt was composed.
+    pub signature_image_id: B256,
+}
+
+impl ComposedJournal {
+    /// Decodes the ABI encoded journal, as `POAPGroup.joinGroupComposed` does.
+    pub fn decode(journal: &[u8]) -> Result<Self> {
+        let (proof_data, block, held_since, ownership_image_id, signature_image_id) =
+            <(ProofData, BlockCommitment, BlockCommitment, B256, B256)>::abi_decode(journal, true)
+                .context("invalid composed journal")?;
+        Ok(Self {
+            journal: Journal {
+                proof_data,
+                block,
+                held_since,
+            },
+            ownership_image_id,
+            signature_image_id,
+        })
+    }
+}
+
+/// The decoded journal of the `is_poap_owner_batch` guest.
+#[derive(Debug)]
+pub struct BatchJournal {
+    pub proof_data: Vec<ProofData>,
+    /// The block the POAP ownership of all members was proven at.
+    pub block: BlockCommitment,
+}
+
+impl BatchJournal {
+    /// Decodes the ABI encoded journal, as `POAPGroup.joinGroupBatch` does.
+    pub fn decode(journal: &[u8]) -> Result<Self> {
+        let (proof_data, block) =
+            <(Vec<ProofData>, BlockCommitment)>::abi_decode_params(journal, true)
+                .context("invalid batch journal")?;
+        Ok(Self { proof_data, block })
+    }
+}
+
+/// The decoded journal of the `is_poap_set_member` guest.
+#[derive(Debug)]
+pub struct SetJournal {
+    pub proof_data: SetProofData,
+    /// The block the POAPs were proven to be held at.
+    pub block: BlockCommitment,
+}
+
+impl SetJournal {
+    /// Decodes the ABI encoded journal, as `POAPSetGroup.joinGroup` does.
+    pub fn decode(journal: &[u8]) -> Result<Self> {
+        let (proof_data, block) = <(SetProofData, BlockCommitment)>::abi_decode(journal, true)
+            .context("invalid set journal")?;
+        Ok(Self { proof_data, block })
+    }
+}
+
+/// The checks of `validateBlock` for a committed block, to reject proofs before submitting them.
+#[async_trait]
+pub trait ValidateBlock {
+    /// Returns an error unless the block is one of the last [BLOCKHASH_WINDOW] blocks before
+    /// `current_number` and has the given hash, as checked with `blockhash` on-chain.
+    fn check_blockhash(&self, current_number: u64, block_hash: B256) -> Result<()>;
+
+    /// Returns an error if the block is more than `max_age` older than the timestamp `now`.
+    fn check_freshness(&self, now: u64, max_age: Duration) -> Result<()>;
+
+    /// Returns an error unless the block has the hash provided by the block hash oracle of the
+    /// group, as checked for blocks of another chain on-chain.
+    fn check_oracle_hash(&self, oracle_hash: Option<B256>) -> Result<()>;
+
+    /// Checks that the block would be accepted by the group at `contract` now.
+    ///
+    /// If the block is from the chain of the `provider`, its hash is checked against one of the
+    /// last [BLOCKHASH_WINDOW] blocks. Otherwise its hash must be provided by the block hash oracle
+    /// of the group, and its age must be at most [MAX_BLOCK_AGE].
+    async fn validate(
+        &self,
+        provider: &Provider<Http>,
+        contract: Address,
+        poap_chain_id: u64,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl ValidateBlock for BlockCommitment {
+    fn check_blockhash(&self, current_number: u64, block_hash: B256) -> Result<()> {
+        let number = self.blockNumber.saturating_to::<u64>();
+        ensure!(
+            number < current_number && current_number - number <= BLOCKHASH_WINDOW,
+            "block {} is not one of the last {} blocks before block {}",
+            number,
+            BLOCKHASH_WINDOW,
+            current_number
+        );
+        ensure!(
+            self.blockHash == block_hash,
+            "hash of block {} does not match the chain",
+            number
+        );
+        Ok(())
+    }
+
+    fn check_freshness(&self, now: u64, max_age: Duration) -> Result<()> {
+        let timestamp = self.blockTimestamp.saturating_to::<u64>();
+        let age = now.saturating_sub(timestamp);
+        ensure!(
+            age <= max_age.as_secs(),
+            "block {} is {}s old, more than {}s",
+            self.blockNumber,
+            age,
+            max_age.as_secs()
+        );
+        Ok(())
+    }
+
+    fn check_oracle_hash(&self, oracle_hash: Option<B256>) -> Result<()> {
+        ensure!(
+            oracle_hash == Some(self.blockHash),
+            "hash of block {} is not provided by the block hash oracle of the group",
+            self.blockNumber
+        );
+        Ok(())
+    }
+
+    async fn validate(
+        &self,
+        provider: &Provider<Http>,
+        contract: Address,
+        poap_chain_id: u64,
+    ) -> Result<()> {
+        let chain_id = provider.get_chainid().await?.as_u64();
+        if poap_chain_id != chain_id {
+            let oracle_hash =
+                oracle_block_hash(provider, contract, poap_chain_id, self.blockNumber).await?;
+            self.check_oracle_hash(oracle_hash)?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            return self.check_freshness(now, MAX_BLOCK_AGE);
+        }
+
+        let current_number = provider.get_block_number().await?.as_u64();
+        let number = self.blockNumber.saturating_to::<u64>();
+        let block_hash = provider
+            .get_block(number)
+            .await?
+            .and_then(|block| block.hash)
+            .with_context(|| format!("block {} not found", number))?;
+        self.check_blockhash(current_number, B256::from(block_hash.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::U256;
+
+    fn commitment(number: u64, timestamp: u64) -> BlockCommitment {
+        BlockCommitment {
+            blockHash: B256::repeat_byte(1),
+            blockNumber: U256::from(number),
+            blockTimestamp: U256::from(timestamp),
+        }
+    }
+
+    #[test]
+    fn check_blockhash() {
+        let commitment = commitment(1000, 0);
+        let hash = B256::repeat_byte(1);
+
+        assert!(commitment.check_blockhash(1001, hash).is_ok());
+        assert!(commitment.check_blockhash(1256, hash).is_ok());
+        assert!(commitment.check_blockhash(1257, hash).is_err());
+        assert!(commitment.check_blockhash(1000, hash).is_err());
+        assert!(commitment.check_blockhash(1001, B256::ZERO).is_err());
+    }
+
+    #[test]
+    fn check_oracle_hash() {
+        let commitment = commitment(1000, 0);
+
+        assert!(commitment
+            .check_oracle_hash(Some(B256::repeat_byte(1)))
+            .is_ok());
+        assert!(commitment
+            .check_oracle_hash(Some(B256::repeat_byte(2)))
+            .is_err());
+        assert!(commitment.check_oracle_hash(None).is_err());
+    }
+
+    #[test]
+    fn decode_batch_journal() {
+        let proof_data = ProofData {
+            eventId: U256::from(1),
+            semaphoreId: U256::from(2),
+            nullifier: B256::repeat_byte(3),
+            chainId: U256::from(100),
+            groupContract: Address::repeat_byte(4),
+            nullifierMode: 0,
+            poapContract: Address::repeat_byte(5),
+            poapChainId: U256::from(100),
+        };
+        let journal =
+            (vec![proof_data.clone(), proof_data], commitment(1000, 0)).abi_encode_params();
+
+        let decoded = BatchJournal::decode(&journal).unwrap();
+        assert_eq!(decoded.proof_data.len(), 2);
+        assert_eq!(decoded.proof_data[1].semaphoreId, U256::from(2));
+        assert_eq!(decoded.block.blockNumber, U256::from(1000));
+    }
+
+    #[test]
+    fn check_freshness() {
+        let commitment = commitment(1000, 10_000);
+
+        assert!(commitment.check_freshness(10_000, MAX_BLOCK_AGE).is_ok());
+        assert!(commitment.check_freshness(13_600, MAX_BLOCK_AGE).is_ok());
+        assert!(commitment.check_freshness(13_601, MAX_BLOCK_AGE).is_err());
+    }
+}
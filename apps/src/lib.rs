@@ -20,141 +20,195 @@
 // under active development. As such, this library might change to adapt to
 // the upstream changes.
 
-use std::time::Duration;
+pub mod bonsai;
+pub mod composition;
+pub mod groth16;
+pub mod group;
+pub mod guest;
+pub mod indexer;
+pub mod journal;
+pub mod message;
+pub mod poap;
+pub mod relayer;
+pub mod semaphore;
+pub mod signal;
+pub mod signer;
+pub mod submission;
+pub mod tx;
+
+use std::path::PathBuf;
 
 use alloy_primitives::FixedBytes;
-use anyhow::{Context, Result};
-use bonsai_sdk::alpha as bonsai_sdk;
-use ethers::prelude::*;
-use risc0_ethereum_contracts::groth16::Seal;
-use risc0_zkvm::{compute_image_id, Receipt};
-
-/// Wrapper of a `SignerMiddleware` client to send transactions to the given
-/// contract's `Address`.
-pub struct TxSender {
-    chain_id: u64,
-    client: SignerMiddleware<Provider<Http>, Wallet<k256::ecdsa::SigningKey>>,
-    contract: Address,
+use anyhow::{ensure, Context, Result};
+use bonsai::{cancel_on_ctrl_c, AsyncBonsaiProver, CtrlCGuard, PollConfig, ProofState};
+use clap::ValueEnum;
+use risc0_zkvm::{default_prover, is_dev_mode, ExecutorEnv, Receipt};
+use thiserror::Error;
+use tokio::runtime::Runtime;
+
+pub use tx::TxSender;
+
+/// A snark proof as the triplet (journal, post-state digest, seal) accepted by the verifier
+/// contract.
+pub type Snark = (Vec<u8>, FixedBytes<32>, Vec<u8>);
+
+/// A backend proving guests, selected with `--prover`.
+pub trait Prover {
+    /// Proves the given elf and input without a snark, resolving each `env::verify` call of the
+    /// guest with one of the given receipts, e.g. to use the receipt as an assumption itself.
+    fn prove_receipt(&self, elf: &[u8], input: &[u8], assumptions: &[Receipt]) -> Result<Receipt>;
+
+    /// Generates a snark proof for the given elf and input, resolving each `env::verify` call of
+    /// the guest with one of the given receipts.
+    ///
+    /// Also returns the receipt of the session, so that it can in turn be used as an assumption.
+    fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        input: &[u8],
+        assumptions: &[Receipt],
+    ) -> Result<(Receipt, Snark)>;
+
+    /// Generates a snark proof as a triplet (`Vec<u8>`, `FixedBytes<32>`,
+    /// `Vec<u8>) for the given elf and input.
+    fn prove(&self, elf: &[u8], input: &[u8]) -> Result<Snark> {
+        let (_receipt, snark) = self.prove_with_assumptions(elf, input, &[])?;
+        Ok(snark)
+    }
 }
 
-impl TxSender {
-    /// Creates a new `TxSender`.
-    pub fn new(chain_id: u64, rpc_url: &str, private_key: &str, contract: &str) -> Result<Self> {
-        let provider = Provider::<Http>::try_from(rpc_url)?;
-        let wallet: LocalWallet = private_key.parse::<LocalWallet>()?.with_chain_id(chain_id);
-        let client = SignerMiddleware::new(provider.clone(), wallet.clone());
-        let contract = contract.parse::<Address>()?;
-
-        Ok(TxSender {
-            chain_id,
-            client,
-            contract,
-        })
+/// The provers selectable with `--prover`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProverKind {
+    /// Prove on Bonsai, which requires `BONSAI_API_URL` and `BONSAI_API_KEY`.
+    Bonsai,
+    /// Prove locally with `default_prover`; no Groth16 seal can be produced.
+    Local,
+    /// Only execute the guest, producing an empty seal accepted by `MockRiscZeroVerifier`.
+    DevMode,
+}
+
+impl ProverKind {
+    /// Returns the prover of this kind. Bonsai is polled with the given configuration, and the
+    /// progress of its proof requests is saved to the state file.
+    pub fn prover(self, poll: PollConfig, state_file: Option<PathBuf>) -> Box<dyn Prover> {
+        match self {
+            ProverKind::Bonsai => Box::new(BonsaiProver { poll, state_file }),
+            ProverKind::Local => Box::new(LocalProver {}),
+            ProverKind::DevMode => Box::new(DevModeProver {}),
+        }
     }
+}
 
-    /// Send a transaction with the given calldata.
-    pub async fn send(&self, calldata: Vec<u8>) -> Result<Option<TransactionReceipt>> {
-        let tx = TransactionRequest::new()
-            .chain_id(self.chain_id)
-            .to(self.contract)
-            .from(self.client.address())
-            .data(calldata);
+/// Returned by provers that cannot produce the Groth16 seal required by the verifier contract.
+#[derive(Debug, Error)]
+#[error(
+    "the {0} prover cannot produce a Groth16 seal; use `--prover bonsai`, or `--prover dev-mode` \
+     with a `MockRiscZeroVerifier`"
+)]
+pub struct Groth16Unavailable(pub &'static str);
+
+/// Builds the environment of a guest with the given input and assumptions.
+fn prover_env<'a>(input: &[u8], assumptions: &[Receipt]) -> Result<ExecutorEnv<'a>> {
+    let mut builder = ExecutorEnv::builder();
+    for receipt in assumptions {
+        builder.add_assumption(receipt.clone());
+    }
+    builder
+        .write_slice(input)
+        .build()
+        .context("failed to build exec env")
+}
 
-        log::info!("Transaction request: {:?}", &tx);
+/// An implementation of a Prover that runs locally with `default_prover`, for teams without
+/// Bonsai credentials or working offline.
+///
+/// Only STARK receipts are produced, since the Groth16 seal requires Bonsai.
+pub struct LocalProver {}
 
-        let tx = self.client.send_transaction(tx, None).await?.await?;
+impl Prover for LocalProver {
+    fn prove_receipt(&self, elf: &[u8], input: &[u8], assumptions: &[Receipt]) -> Result<Receipt> {
+        default_prover().prove(prover_env(input, assumptions)?, elf)
+    }
 
-        log::info!("Transaction receipt: {:?}", &tx);
+    fn prove_with_assumptions(
+        &self,
+        _elf: &[u8],
+        _input: &[u8],
+        _assumptions: &[Receipt],
+    ) -> Result<(Receipt, Snark)> {
+        // Fail before proving, rather than after a long local proof.
+        Err(Groth16Unavailable("local").into())
+    }
+}
 
-        Ok(tx)
+/// An implementation of a Prover that only executes the guest, for development.
+///
+/// Requires `RISC0_DEV_MODE=1`, so that receipts are fake and accepted as assumptions. The seal is
+/// empty and the post-state digest zero, as expected by `MockRiscZeroVerifier`.
+pub struct DevModeProver {}
+
+impl Prover for DevModeProver {
+    fn prove_receipt(&self, elf: &[u8], input: &[u8], assumptions: &[Receipt]) -> Result<Receipt> {
+        ensure!(
+            is_dev_mode(),
+            "the dev-mode prover requires RISC0_DEV_MODE=1"
+        );
+        default_prover().prove(prover_env(input, assumptions)?, elf)
     }
+
+    fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        input: &[u8],
+        assumptions: &[Receipt],
+    ) -> Result<(Receipt, Snark)> {
+        let receipt = self.prove_receipt(elf, input, assumptions)?;
+        let journal = receipt.journal.bytes.clone();
+        Ok((receipt, (journal, FixedBytes::<32>::default(), Vec::new())))
+    }
+}
+
+/// An implementation of a Prover that runs on Bonsai, blocking on an [AsyncBonsaiProver].
+pub struct BonsaiProver {
+    pub poll: PollConfig,
+    /// File the progress of proof requests is saved to, see [ProofState].
+    pub state_file: Option<PathBuf>,
 }
 
-/// An implementation of a Prover that runs on Bonsai.
-pub struct BonsaiProver {}
 impl BonsaiProver {
-    /// Generates a snark proof as a triplet (`Vec<u8>`, `FixedBytes<32>`,
-    /// `Vec<u8>) for the given elf and input.
-    pub fn prove(elf: &[u8], input: &[u8]) -> Result<(Vec<u8>, FixedBytes<32>, Vec<u8>)> {
-        let client = bonsai_sdk::Client::from_env(risc0_zkvm::VERSION)?;
-
-        // Compute the image_id, then upload the ELF with the image_id as its key.
-        let image_id = compute_image_id(elf)?;
-        let image_id_hex = image_id.to_string();
-        client.upload_img(&image_id_hex, elf.to_vec())?;
-        log::info!("Image ID: 0x{}", image_id_hex);
-
-        // Prepare input data and upload it.
-        let input_id = client.upload_input(input.to_vec())?;
-
-        // Start a session running the prover.
-        let session = client.create_session(image_id_hex, input_id, vec![])?;
-        log::info!("Created session: {}", session.uuid);
-        let _receipt = loop {
-            let res = session.status(&client)?;
-            if res.status == "RUNNING" {
-                log::info!(
-                    "Current status: {} - state: {} - continue polling...",
-                    res.status,
-                    res.state.unwrap_or_default()
-                );
-                std::thread::sleep(Duration::from_secs(15));
-                continue;
-            }
-            if res.status == "SUCCEEDED" {
-                // Download the receipt, containing the output.
-                let receipt_url = res
-                    .receipt_url
-                    .context("API error, missing receipt on completed session")?;
-
-                let receipt_buf = client.download(&receipt_url)?;
-                let receipt: Receipt = bincode::deserialize(&receipt_buf)?;
-
-                break receipt;
-            }
-
-            panic!(
-                "Workflow exited: {} - | err: {}",
-                res.status,
-                res.error_msg.unwrap_or_default()
-            );
-        };
-
-        // Fetch the snark.
-        let snark_session = client.create_snark(session.uuid)?;
-        log::info!("Created snark session: {}", snark_session.uuid);
-        let snark_receipt = loop {
-            let res = snark_session.status(&client)?;
-            match res.status.as_str() {
-                "RUNNING" => {
-                    log::info!("Current status: {} - continue polling...", res.status,);
-                    std::thread::sleep(Duration::from_secs(15));
-                    continue;
-                }
-                "SUCCEEDED" => {
-                    break res.output.context("No snark generated :(")?;
-                }
-                _ => {
-                    panic!(
-                        "Workflow exited: {} err: {}",
-                        res.status,
-                        res.error_msg.unwrap_or_default()
-                    );
-                }
-            }
-        };
-
-        let snark = snark_receipt.snark;
-        log::debug!("Snark proof!: {snark:?}");
-
-        let seal = Seal::abi_encode(snark).context("Read seal")?;
-        let post_state_digest: FixedBytes<32> = snark_receipt
-            .post_state_digest
-            .as_slice()
-            .try_into()
-            .context("Read post_state_digest")?;
-        let journal = snark_receipt.journal;
-
-        Ok((journal, post_state_digest, seal))
+    /// Resumes the proof request saved in the state file, up to the snark proof.
+    pub fn resume(&self, state: ProofState) -> Result<(Receipt, Snark)> {
+        let (runtime, prover, _ctrl_c) = self.start()?;
+        Ok(runtime.block_on(prover.resume(state))?)
+    }
+
+    /// Returns a runtime and an async prover that is cancelled on Ctrl-C while the returned guard
+    /// lives, so that the request can be resumed from the state file.
+    fn start(&self) -> Result<(Runtime, AsyncBonsaiProver, CtrlCGuard)> {
+        let runtime = Runtime::new().context("failed to start runtime")?;
+        let ctrl_c = cancel_on_ctrl_c();
+        let mut prover = AsyncBonsaiProver::new(self.poll.clone(), ctrl_c.token());
+        if let Some(path) = &self.state_file {
+            prover = prover.with_state_file(path.clone());
+        }
+        Ok((runtime, prover, ctrl_c))
+    }
+}
+
+impl Prover for BonsaiProver {
+    fn prove_receipt(&self, elf: &[u8], input: &[u8], assumptions: &[Receipt]) -> Result<Receipt> {
+        let (runtime, prover, _ctrl_c) = self.start()?;
+        Ok(runtime.block_on(prover.prove_receipt(elf, input, assumptions))?)
+    }
+
+    fn prove_with_assumptions(
+        &self,
+        elf: &[u8],
+        input: &[u8],
+        assumptions: &[Receipt],
+    ) -> Result<(Receipt, Snark)> {
+        let (runtime, prover, _ctrl_c) = self.start()?;
+        Ok(runtime.block_on(prover.prove_with_assumptions(elf, input, assumptions))?)
     }
 }
@@ -0,0 +1,117 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signatures of the messages binding a POAP holder to a group, as produced by wallets.
+//!
+//! The messages are built from the guest inputs in `zkpoap-types`, e.g.
+//! [GroupBinding::signing_hash](zkpoap_types::GroupBinding::signing_hash), so that the host signs
+//! exactly what the guest verifies.
+
+use alloy_primitives::B256;
+use anyhow::{Context, Result};
+use k256::ecdsa::SigningKey;
+
+/// Signs the hash of a message and returns the 65-byte `r || s || v` signature, as produced by
+/// wallets.
+pub fn sign(signing_hash: B256, signing_key: &SigningKey) -> Result<Vec<u8>> {
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(signing_hash.as_slice())
+        .context("failed to sign message")?;
+
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, U256};
+    use zkpoap_types::{
+        recover_owner, GroupBinding, MessageFormat, NullifierMode, SemaphoreIdentity, SetBinding,
+    };
+
+    fn binding() -> GroupBinding {
+        GroupBinding {
+            event_id: U256::from(1),
+            group_contract: Address::ZERO,
+            chain_id: 100,
+            semaphore_identity: SemaphoreIdentity::Commitment(U256::ZERO),
+            nullifier_mode: NullifierMode::Address,
+        }
+    }
+
+    #[test]
+    fn recover_signer() {
+        let signing_key = SigningKey::from_slice(&U256::from(1).to_be_bytes::<32>()).unwrap();
+        let signer = Address::from_public_key(signing_key.verifying_key());
+
+        let binding = binding();
+        for format in [
+            MessageFormat::Eip191,
+            MessageFormat::Eip712 {
+                deadline: U256::MAX,
+            },
+        ] {
+            let hash = binding.signing_hash(&format);
+            let signature = sign(hash, &signing_key).unwrap();
+            assert_eq!(signature.len(), 65);
+            assert_eq!(recover_owner(&signature, &hash).unwrap(), signer);
+        }
+    }
+
+    #[test]
+    fn signature_is_bound_to_group() {
+        let signing_key = SigningKey::from_slice(&U256::from(1).to_be_bytes::<32>()).unwrap();
+        let signer = Address::from_public_key(signing_key.verifying_key());
+
+        let binding = binding();
+        let format = MessageFormat::Eip191;
+        let signature = sign(binding.signing_hash(&format), &signing_key).unwrap();
+
+        let other = GroupBinding {
+            event_id: U256::from(2),
+            ..binding
+        };
+        let recovered = recover_owner(&signature, &other.signing_hash(&format)).unwrap();
+        assert_ne!(recovered, signer);
+    }
+
+    #[test]
+    fn set_signature_is_bound_to_set() {
+        let signing_key = SigningKey::from_slice(&U256::from(1).to_be_bytes::<32>()).unwrap();
+        let signer = Address::from_public_key(signing_key.verifying_key());
+
+        let binding = SetBinding {
+            event_ids: vec![U256::from(1), U256::from(2)],
+            threshold: 1,
+            group_contract: Address::ZERO,
+            chain_id: 100,
+            semaphore_identity: SemaphoreIdentity::Commitment(U256::ZERO),
+        };
+        let format = MessageFormat::Eip712 {
+            deadline: U256::MAX,
+        };
+        let signature = sign(binding.signing_hash(&format), &signing_key).unwrap();
+        let recovered = recover_owner(&signature, &binding.signing_hash(&format)).unwrap();
+        assert_eq!(recovered, signer);
+
+        let other = SetBinding {
+            threshold: 2,
+            ..binding.clone()
+        };
+        let recovered = recover_owner(&signature, &other.signing_hash(&format)).unwrap();
+        assert_ne!(recovered, signer);
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! POAP contract deployments queried by the guest.
+
+use alloy_primitives::{address, Address, U256};
+use anyhow::{bail, Result};
+use risc0_ethereum_view_call::{ethereum::EthViewCallEnv, ViewCall};
+use zkpoap_types::{PoapContract, POAP};
+
+/// Address of the POAP contract on Gnosis Chain and Ethereum Mainnet.
+pub const POAP_CONTRACT: Address = address!("22C1f6050E56d2876009903609a2cC3fEf83B415");
+
+/// Searches the POAPs of `owner` at the given block for one from the given event, and returns its
+/// index in the enumeration of `tokenDetailsOfOwnerByIndex`.
+///
+/// The search runs in its own environment, so that only the call for the returned index needs
+/// to be preflighted for the guest.
+pub fn find_poap_index(
+    rpc_url: &str,
+    block_number: u64,
+    poap: &PoapContract,
+    owner: Address,
+    event_id: U256,
+) -> Result<U256> {
+    let mut env =
+        EthViewCallEnv::from_rpc(rpc_url, Some(block_number))?.with_chain_spec(poap.chain_spec()?);
+
+    let balance = ViewCall::new(POAP::balanceOfCall { owner }, poap.address)
+        .with_caller(owner)
+        .preflight_call(&mut env)?
+        ._0;
+    for index in 0..balance.saturating_to::<u64>() {
+        let index = U256::from(index);
+        let call = POAP::tokenDetailsOfOwnerByIndexCall { owner, index };
+        let returns = ViewCall::new(call, poap.address)
+            .with_caller(owner)
+            .preflight_call(&mut env)?;
+        if returns._1 == event_id {
+            log::info!(
+                "Found POAP {} from event {} at index {}",
+                returns._0,
+                event_id,
+                index
+            );
+            return Ok(index);
+        }
+    }
+
+    bail!(
+        "{} holds no POAP from event {} at block {} (checked {} POAPs)",
+        owner,
+        event_id,
+        block_number,
+        balance
+    )
+}
+
+/// Searches the POAPs of `owner` at the given block for POAPs from the given events, and returns
+/// the index of one POAP per event held, as passed to the `is_poap_set_member` guest.
+pub fn find_poap_indices(
+    rpc_url: &str,
+    block_number: u64,
+    poap: &PoapContract,
+    owner: Address,
+    event_ids: &[U256],
+) -> Result<Vec<U256>> {
+    let mut env =
+        EthViewCallEnv::from_rpc(rpc_url, Some(block_number))?.with_chain_spec(poap.chain_spec()?);
+
+    let balance = ViewCall::new(POAP::balanceOfCall { owner }, poap.address)
+        .with_caller(owner)
+        .preflight_call(&mut env)?
+        ._0;
+    let mut found = Vec::new();
+    let mut indices = Vec::new();
+    for index in 0..balance.saturating_to::<u64>() {
+        let index = U256::from(index);
+        let call = POAP::tokenDetailsOfOwnerByIndexCall { owner, index };
+        let returns = ViewCall::new(call, poap.address)
+            .with_caller(owner)
+            .preflight_call(&mut env)?;
+        // Further POAPs from an event already found do not count towards the threshold.
+        if event_ids.contains(&returns._1) && !found.contains(&returns._1) {
+            log::info!(
+                "Found POAP {} from event {} at index {}",
+                returns._0,
+                returns._1,
+                index
+            );
+            found.push(returns._1);
+            indices.push(index);
+        }
+    }
+
+    Ok(indices)
+}
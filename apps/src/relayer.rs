@@ -0,0 +1,335 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Relayed submission of proofs: the relayer sends the `POAPGroup` transaction and pays for its
+//! gas, so that the holder needs no funded wallet and does not send the transaction from their
+//! own address.
+//!
+//! This does not make joining unlinkable. The nullifier in the calldata is deterministic: in
+//! address mode it is `keccak256(abi.encode(owner, groupId))`, which anyone can compute for the
+//! known holders of the event, and in token mode it is keyed on a POAP whose owner is public.
+//!
+//! The API follows the one of `risc0-ethereum-relay`, with proofs posted as JSON to
+//! [SUBMISSIONS_ROUTE] and errors returned as plain text.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy_primitives::B256;
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use ethers::{signers::Signer, types::U256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    submission::Submission,
+    tx::{TxError, TxSender},
+};
+
+/// Route the proofs are posted to.
+pub const SUBMISSIONS_ROUTE: &str = "/v1/submissions";
+
+/// Response of the relayer to an accepted submission.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmissionResponse {
+    /// Hash of the broadcast `POAPGroup` transaction. The relayer replaces it with higher fees
+    /// while it is stuck, so the transaction that is mined may have another hash.
+    pub tx_hash: B256,
+}
+
+/// Errors of the relayer, returned to the client with the status of [RelayerError::status_code].
+#[derive(Debug, Error)]
+pub enum RelayerError {
+    #[error("invalid submission: {0:#}")]
+    InvalidSubmission(anyhow::Error),
+    #[error("too many submissions, retry in {0:?}")]
+    RateLimited(Duration),
+    #[error(transparent)]
+    Transaction(#[from] TxError),
+    #[error("failed to send the transaction: {0:#}")]
+    Internal(anyhow::Error),
+}
+
+impl RelayerError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            // The proof is invalid, or rejected by `POAPGroup`, e.g. as a duplicate.
+            RelayerError::InvalidSubmission(_)
+            | RelayerError::Transaction(TxError::WouldRevert(_)) => StatusCode::BAD_REQUEST,
+            RelayerError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            RelayerError::Transaction(_) | RelayerError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl IntoResponse for RelayerError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        if status.is_server_error() {
+            log::error!("Error response: {}", self);
+        }
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Limits the number of submissions of each client IP address in a fixed window, since every
+/// submission costs a Groth16 verification and a gas estimation.
+///
+/// Behind a reverse proxy all clients share its address, so the proxy should limit them instead.
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    clients: std::sync::Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    /// Allows `max_requests` submissions per client in each `window`.
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            clients: Default::default(),
+        }
+    }
+
+    /// Counts a submission of the client, and returns [RelayerError::RateLimited] with the time
+    /// until its window ends if it exceeds the limit.
+    fn check(&self, client: IpAddr, now: Instant) -> Result<(), RelayerError> {
+        let mut clients = self.clients.lock().unwrap();
+        // Forget the clients whose window ended, so that the map does not grow without bound.
+        clients.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+
+        let (start, count) = clients.entry(client).or_insert((now, 0));
+        if *count >= self.max_requests {
+            return Err(RelayerError::RateLimited(
+                self.window - now.duration_since(*start),
+            ));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// State of the relayer: the sender of its transactions and the nonce of the next one.
+pub struct RelayerState<S> {
+    sender: Arc<TxSender<S>>,
+    // Transactions are broadcast one at a time, each with the next nonce of the wallet. `None`
+    // until read from the node, and after a failed broadcast, which may not have used the nonce.
+    nonce: Arc<Mutex<Option<U256>>>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> RelayerState<S> {
+    pub fn new(sender: TxSender<S>, limiter: RateLimiter) -> Self {
+        Self {
+            sender: Arc::new(sender),
+            nonce: Arc::new(Mutex::new(None)),
+            limiter: Arc::new(limiter),
+        }
+    }
+}
+
+impl<S> Clone for RelayerState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            nonce: self.nonce.clone(),
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Returns the router of the relayer API.
+///
+/// The rate limit needs the address of the client, so the router must be served with
+/// `into_make_service_with_connect_info::<SocketAddr>`.
+pub fn app<S: Signer + 'static>(state: RelayerState<S>) -> Router {
+    Router::new()
+        .route(SUBMISSIONS_ROUTE, post(post_submission))
+        .with_state(state)
+}
+
+/// Serves the relayer API on the given port.
+pub async fn serve<S: Signer + 'static>(state: RelayerState<S>, port: u16) -> Result<()> {
+    let bind_address = format!("0.0.0.0:{port}");
+    let listener = tokio::net::TcpListener::bind(&bind_address)
+        .await
+        .with_context(|| format!("failed to bind {bind_address}"))?;
+    log::info!("Relayer listening on {}", bind_address);
+    axum::serve(
+        listener,
+        app(state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .with_context(|| format!("failed to serve API on {bind_address}"))
+}
+
+/// Verifies the posted proof and submits it to `POAPGroup`, returning the hash of the transaction
+/// once broadcast. The transaction is then replaced with higher fees in the background while it
+/// is stuck.
+async fn post_submission<S: Signer + 'static>(
+    State(state): State<RelayerState<S>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Json(submission): Json<Submission>,
+) -> Result<Json<SubmissionResponse>, RelayerError> {
+    state.limiter.check(client.ip(), Instant::now())?;
+
+    // The relayer pays for the transaction, so invalid proofs are rejected before sending it. The
+    // Groth16 verification is CPU-bound, so it does not run on the runtime threads.
+    let calldata = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+        submission.verify()?;
+        let (_block, calldata) = submission.calldata()?;
+        Ok(calldata)
+    })
+    .await
+    .map_err(|err| RelayerError::Internal(err.into()))?
+    .map_err(RelayerError::InvalidSubmission)?;
+
+    let mut tx = state.sender.prepare(calldata).await.map_err(tx_error)?;
+    let tx_hash = {
+        let mut nonce = state.nonce.lock().await;
+        let next = match *nonce {
+            Some(next) => next,
+            None => state.sender.pending_nonce().await.map_err(tx_error)?,
+        };
+        tx.set_nonce(next);
+        match state.sender.broadcast(&tx).await {
+            Ok(tx_hash) => {
+                *nonce = Some(next + 1);
+                tx_hash
+            }
+            Err(err) => {
+                *nonce = None;
+                return Err(tx_error(err));
+            }
+        }
+    };
+    log::info!("Submitted proof in transaction {:?}", tx_hash);
+
+    // Later submissions do not wait for this transaction, which keeps its nonce when replaced.
+    let sender = state.sender.clone();
+    tokio::spawn(async move {
+        match sender.confirm(tx, tx_hash).await {
+            Ok(receipt) => log::info!("Mined transaction {:?}", receipt.transaction_hash),
+            Err(err) => log::error!("Transaction {:?} failed: {:#}", tx_hash, err),
+        }
+    });
+
+    Ok(Json(SubmissionResponse {
+        tx_hash: tx_hash.0.into(),
+    }))
+}
+
+/// Maps an error of [TxSender] to the response of the relayer.
+fn tx_error(err: anyhow::Error) -> RelayerError {
+    match err.downcast::<TxError>() {
+        Ok(err) => RelayerError::Transaction(err),
+        Err(err) => RelayerError::Internal(err),
+    }
+}
+
+/// Client of a relayer, posting proofs to its [SUBMISSIONS_ROUTE].
+pub struct RelayerClient {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl RelayerClient {
+    /// Creates a client of the relayer at the given base URL.
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Posts the proof to the relayer and returns the hash of the transaction it was submitted
+    /// in. The relayer responds once the transaction is broadcast, before it is mined.
+    pub async fn submit(&self, submission: &Submission) -> Result<B256> {
+        let url = format!("{}{}", self.url, SUBMISSIONS_ROUTE);
+        let response = self
+            .client
+            .post(&url)
+            .json(submission)
+            .send()
+            .await
+            .with_context(|| format!("failed to reach the relayer at {}", url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            bail!("the relayer rejected the proof ({}): {}", status, message);
+        }
+        let response: SubmissionResponse =
+            response.json().await.context("invalid relayer response")?;
+        Ok(response.tx_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::tx::RevertReason;
+
+    #[test]
+    fn status_codes() {
+        let err = RelayerError::InvalidSubmission(anyhow!("invalid seal"));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        let err = RelayerError::Transaction(TxError::WouldRevert(RevertReason::Empty));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        let err = RelayerError::Transaction(TxError::NotMined(5));
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let err = RelayerError::RateLimited(Duration::from_secs(1));
+        assert_eq!(err.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn rate_limit() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let client = IpAddr::from([127, 0, 0, 1]);
+        let other = IpAddr::from([127, 0, 0, 2]);
+        let now = Instant::now();
+
+        limiter.check(client, now).unwrap();
+        limiter.check(client, now).unwrap();
+        assert!(matches!(
+            limiter.check(client, now + Duration::from_secs(10)),
+            Err(RelayerError::RateLimited(retry)) if retry == Duration::from_secs(50)
+        ));
+        // Clients are limited separately, and again allowed once their window ended.
+        limiter.check(other, now).unwrap();
+        limiter
+            .check(client, now + Duration::from_secs(60))
+            .unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Semaphore identities of POAP holders.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+use alloy_primitives::U256;
+use anyhow::{Context, Result};
+use rand_core::{OsRng, RngCore};
+use zkpoap_types::{poseidon, SemaphoreIdentity};
+
+/// A Semaphore identity, compatible with `@semaphore-protocol/identity` v3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub trapdoor: U256,
+    pub nullifier: U256,
+}
+
+impl Identity {
+    /// Generates a new identity from random 31-byte secrets, which always fit the BN254 field.
+    pub fn random() -> Self {
+        Self {
+            trapdoor: random_secret(),
+            nullifier: random_secret(),
+        }
+    }
+
+    /// Returns the identity secret `poseidon(nullifier, trapdoor)`.
+    pub fn secret(&self) -> U256 {
+        poseidon(&[self.nullifier, self.trapdoor])
+    }
+
+    /// Returns the identity commitment `poseidon(secret)` added to the group.
+    pub fn commitment(&self) -> U256 {
+        poseidon(&[self.secret()])
+    }
+
+    /// Passes the secrets of the identity to the guest; see [SemaphoreIdentity::Secret].
+    ///
+    /// The guest input is uploaded to Bonsai and written to the input file of `preflight`, so
+    /// this is only used on request.
+    pub fn to_secret_input(&self) -> SemaphoreIdentity {
+        SemaphoreIdentity::Secret {
+            trapdoor: self.trapdoor,
+            nullifier: self.nullifier,
+        }
+    }
+
+    /// Loads an identity from a file, as written by [Identity::save].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read identity file {}", path.display()))?;
+        let (trapdoor, nullifier) = serde_json::from_str(&json).context("invalid identity file")?;
+        Ok(Self {
+            trapdoor,
+            nullifier,
+        })
+    }
+
+    /// Saves the identity to a file as the JSON array `["<trapdoor>", "<nullifier>"]`, which is
+    /// the format of `Identity.toString()` in `@semaphore-protocol/identity` v3.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_string(&(self.trapdoor, self.nullifier))?;
+        create_secret_file(path)?
+            .write_all(json.as_bytes())
+            .with_context(|| format!("failed to write identity file {}", path.display()))
+    }
+
+    /// Loads the identity from a file, or generates a new one and saves it if the file does not
+    /// exist.
+    pub fn load_or_create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        let identity = Self::random();
+        identity.save(path)?;
+        log::info!("Created new Semaphore identity: {}", path.display());
+
+        Ok(identity)
+    }
+}
+
+/// Passes only the commitment of the identity, computed on the host, to the guest.
+impl From<Identity> for SemaphoreIdentity {
+    fn from(identity: Identity) -> Self {
+        SemaphoreIdentity::Commitment(identity.commitment())
+    }
+}
+
+/// Creates a file only readable by its owner, for the secrets of an identity.
+///
+/// An existing file is removed first rather than truncated, since the permissions only apply to
+/// new files and it may have been created readable by others.
+pub fn create_secret_file(path: &Path) -> Result<File> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            return Err(err).with_context(|| format!("failed to replace {}", path.display()));
+        }
+        _ => {}
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .with_context(|| format!("failed to create {}", path.display()))
+}
+
+fn random_secret() -> U256 {
+    let mut bytes = [0u8; 31];
+    OsRng.fill_bytes(&mut bytes);
+    U256::from_be_slice(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join("zkpoap-identity-test.json");
+        let identity = Identity::random();
+        // An existing file, created with the default permissions, is replaced.
+        std::fs::write(&path, "").unwrap();
+        identity.save(&path).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = Identity::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, identity);
+        assert_eq!(loaded.to_secret_input().commitment(), identity.commitment());
+        assert!(matches!(
+            SemaphoreIdentity::from(loaded),
+            SemaphoreIdentity::Commitment(commitment) if commitment == identity.commitment()
+        ));
+    }
+}
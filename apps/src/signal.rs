@@ -0,0 +1,255 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Semaphore signals of group members, validated by `POAPGroup.validateSignal`.
+//!
+//! The Groth16 proof of the Semaphore v3 circuit is generated by snarkjs, from the inputs built
+//! here and the `semaphore.wasm` and `semaphore.zkey` artifacts of depth 20.
+
+use std::{io::Write, path::Path, process::Command};
+
+use alloy_primitives::{keccak256, U256};
+use alloy_sol_types::SolCall;
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use zkpoap_types::poseidon;
+
+use crate::{
+    group::{IPOAPGroup, MerkleTree},
+    semaphore::Identity,
+};
+
+/// Hashes a signal or a scope to a field element, as `SemaphoreVerifier` does before verifying
+/// the proof.
+pub fn hash_to_field(value: U256) -> U256 {
+    U256::from_be_bytes(keccak256(value.to_be_bytes::<32>()).0) >> 8
+}
+
+/// Returns the nullifier hash of the identity for the scope, which is the same for all signals of
+/// a member in a scope.
+pub fn nullifier_hash(identity: &Identity, scope: U256) -> U256 {
+    poseidon(&[hash_to_field(scope), identity.nullifier])
+}
+
+/// Inputs of the Semaphore v3 circuit, in the `input.json` format of snarkjs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitInputs {
+    pub identity_trapdoor: U256,
+    pub identity_nullifier: U256,
+    pub tree_path_indices: Vec<u8>,
+    pub tree_siblings: Vec<U256>,
+    pub external_nullifier: U256,
+    pub signal_hash: U256,
+}
+
+/// A signal of a member in a scope, waiting for its Groth16 proof.
+#[derive(Debug, Clone)]
+pub struct SignalInputs {
+    pub signal: U256,
+    pub scope: U256,
+    pub merkle_tree_root: U256,
+    pub nullifier_hash: U256,
+    pub circuit: CircuitInputs,
+}
+
+impl SignalInputs {
+    /// Builds the inputs of a signal of the identity, which must be a member of the tree.
+    pub fn new(identity: &Identity, tree: &MerkleTree, scope: U256, signal: U256) -> Result<Self> {
+        let commitment = identity.commitment();
+        let index = tree
+            .index_of(commitment)
+            .with_context(|| format!("identity {} is not a member of the group", commitment))?;
+        let proof = tree.proof(index)?;
+
+        Ok(Self {
+            signal,
+            scope,
+            merkle_tree_root: proof.root,
+            nullifier_hash: nullifier_hash(identity, scope),
+            circuit: CircuitInputs {
+                identity_trapdoor: identity.trapdoor,
+                identity_nullifier: identity.nullifier,
+                tree_path_indices: proof.path_indices,
+                tree_siblings: proof.siblings,
+                external_nullifier: hash_to_field(scope),
+                signal_hash: hash_to_field(signal),
+            },
+        })
+    }
+
+    /// Returns the signal with the given proof, as taken by `POAPGroup.validateSignal`.
+    pub fn signal(&self, proof: [U256; 8]) -> IPOAPGroup::Signal {
+        IPOAPGroup::Signal {
+            signal: self.signal,
+            scope: self.scope,
+            merkleTreeRoot: self.merkle_tree_root,
+            nullifierHash: self.nullifier_hash,
+            proof,
+        }
+    }
+
+    /// Returns the calldata of `POAPGroup.validateSignal` with the given proof.
+    pub fn calldata(&self, proof: [U256; 8]) -> Vec<u8> {
+        IPOAPGroup::validateSignalCall {
+            signal: self.signal(proof),
+        }
+        .abi_encode()
+    }
+}
+
+/// A Groth16 proof in the `proof.json` format of snarkjs, with decimal coordinates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnarkjsProof {
+    pub pi_a: Vec<String>,
+    pub pi_b: Vec<Vec<String>>,
+    pub pi_c: Vec<String>,
+}
+
+impl SnarkjsProof {
+    /// Loads a proof written by `snarkjs groth16 prove` or `fullprove`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read proof {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("invalid proof {}", path.display()))
+    }
+
+    /// Packs the proof into the `uint256[8]` of `SemaphoreVerifier`, where the coordinates of the
+    /// G2 point are swapped to the order of the pairing precompile.
+    pub fn pack(&self) -> Result<[U256; 8]> {
+        ensure!(
+            self.pi_a.len() >= 2
+                && self.pi_c.len() >= 2
+                && self.pi_b.len() >= 2
+                && self.pi_b[..2]
+                    .iter()
+                    .all(|coordinate| coordinate.len() == 2),
+            "incomplete Groth16 proof"
+        );
+        let values = [
+            &self.pi_a[0],
+            &self.pi_a[1],
+            &self.pi_b[0][1],
+            &self.pi_b[0][0],
+            &self.pi_b[1][1],
+            &self.pi_b[1][0],
+            &self.pi_c[0],
+            &self.pi_c[1],
+        ];
+
+        let mut proof = [U256::ZERO; 8];
+        for (packed, value) in proof.iter_mut().zip(values) {
+            *packed = value
+                .parse()
+                .with_context(|| format!("invalid proof coordinate {}", value))?;
+        }
+        Ok(proof)
+    }
+}
+
+/// Proves the circuit inputs with `snarkjs groth16 fullprove`, which must be installed, using the
+/// given circuit and proving key.
+pub fn prove_with_snarkjs(
+    inputs: &CircuitInputs,
+    wasm: &Path,
+    zkey: &Path,
+) -> Result<SnarkjsProof> {
+    // The inputs contain the secrets of the identity, so they are written to a file only readable
+    // by the user, in a directory with a random name that is removed on every path.
+    let dir = tempfile::Builder::new()
+        .prefix("zkpoap-signal-")
+        .tempdir()
+        .context("failed to create a temporary directory")?;
+    let mut input = tempfile::Builder::new()
+        .suffix(".json")
+        .tempfile_in(dir.path())
+        .context("failed to create the input file")?;
+    serde_json::to_writer(&mut input, inputs)?;
+    input.flush()?;
+    let proof = dir.path().join("proof.json");
+    let public = dir.path().join("public.json");
+
+    let status = Command::new("snarkjs")
+        .args(["groth16", "fullprove"])
+        .arg(input.path())
+        .arg(wasm)
+        .arg(zkey)
+        .arg(&proof)
+        .arg(&public)
+        .status()
+        .context("failed to run snarkjs; install it with `npm install -g snarkjs`")?;
+    ensure!(status.success(), "snarkjs failed with {}", status);
+
+    SnarkjsProof::load(&proof)
+}
+
+/// Returns the proof of the signal, read from `proof` if given, or else generated with snarkjs
+/// from `wasm` and `zkey`.
+pub fn signal_proof(
+    inputs: &SignalInputs,
+    proof: Option<&Path>,
+    artifacts: Option<(&Path, &Path)>,
+) -> Result<[U256; 8]> {
+    let proof = match (proof, artifacts) {
+        (Some(path), _) => SnarkjsProof::load(path)?,
+        (None, Some((wasm, zkey))) => prove_with_snarkjs(&inputs.circuit, wasm, zkey)?,
+        (None, None) => bail!("no Semaphore proof given and no circuit to generate it"),
+    };
+    proof.pack()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_to_field_shifts_keccak() {
+        let expected = "0x00290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e5";
+        assert_eq!(hash_to_field(U256::ZERO), expected.parse::<U256>().unwrap());
+    }
+
+    #[test]
+    fn pack_swaps_g2_coordinates() {
+        let proof = SnarkjsProof {
+            pi_a: vec!["1".into(), "2".into(), "1".into()],
+            pi_b: vec![
+                vec!["3".into(), "4".into()],
+                vec!["5".into(), "6".into()],
+                vec!["1".into(), "0".into()],
+            ],
+            pi_c: vec!["7".into(), "8".into(), "1".into()],
+        };
+        let packed = proof.pack().unwrap();
+        assert_eq!(packed, [1u64, 2, 4, 3, 6, 5, 7, 8].map(U256::from));
+    }
+
+    #[test]
+    fn signal_of_member() {
+        let identity = Identity::random();
+        let mut tree = MerkleTree::new(U256::ZERO);
+        tree.insert(U256::from(1)).unwrap();
+        assert!(SignalInputs::new(&identity, &tree, U256::from(2), U256::from(3)).is_err());
+
+        tree.insert(identity.commitment()).unwrap();
+        let inputs = SignalInputs::new(&identity, &tree, U256::from(2), U256::from(3)).unwrap();
+        assert_eq!(inputs.merkle_tree_root, tree.root());
+        assert_eq!(inputs.circuit.tree_path_indices[0], 1);
+        assert_eq!(inputs.circuit.tree_siblings[0], U256::from(1));
+        assert_eq!(
+            inputs.nullifier_hash,
+            nullifier_hash(&identity, U256::from(2))
+        );
+    }
+}
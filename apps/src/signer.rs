@@ -0,0 +1,439 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signers of the transactions sent by [TxSender](crate::TxSender): encrypted keystores, AWS KMS
+//! keys and remote signers over JSON-RPC.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use clap::{ArgGroup, Args};
+use ethers::{
+    core::k256::ecdsa::SigningKey,
+    prelude::*,
+    types::transaction::{
+        eip2718::{TypedTransaction, TypedTransactionError},
+        eip712::Eip712,
+    },
+    utils::rlp::Rlp,
+};
+use ethers_signers::{AwsSigner, AwsSignerError};
+use risc0_ethereum_relay::client_config::WalletKey;
+use rusoto_kms::KmsClient;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Errors of the [RemoteSigner].
+#[derive(Debug, Error)]
+pub enum RemoteSignerError {
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+    #[error("invalid signature: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("invalid signed transaction: {0}")]
+    Transaction(#[from] TypedTransactionError),
+    #[error("the remote signer signed another transaction")]
+    TransactionMismatch,
+    #[error("the remote signer signed with {0:?}, not with the selected address")]
+    WrongSigner(Address),
+    #[error("EIP-712 signing is not supported by the remote signer")]
+    TypedDataUnsupported,
+}
+
+/// Response of `eth_signTransaction`: the raw transaction for Web3Signer, and an object with the
+/// raw transaction for Clef.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignedTransaction {
+    Raw(Bytes),
+    Object { raw: Bytes },
+}
+
+/// A signer delegating to an external signer, such as Clef or Web3Signer, over JSON-RPC, so that
+/// the key never leaves the signer.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    provider: Provider<Http>,
+    address: Address,
+    chain_id: u64,
+}
+
+impl RemoteSigner {
+    /// Connects to the signer at the given endpoint, signing with the given address or with the
+    /// first account of the signer.
+    pub async fn connect(url: &str, address: Option<Address>, chain_id: u64) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(url)?;
+        let address = match address {
+            Some(address) => address,
+            None => *provider
+                .get_accounts()
+                .await?
+                .first()
+                .context("the remote signer has no accounts")?,
+        };
+        Ok(Self {
+            provider,
+            address,
+            chain_id,
+        })
+    }
+
+    /// Returns an error unless the signature was recovered to the address of the signer.
+    fn check_signer(&self, signer: Address) -> Result<(), RemoteSignerError> {
+        if signer != self.address {
+            return Err(RemoteSignerError::WrongSigner(signer));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    type Error = RemoteSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        let message = Bytes::from(message.as_ref().to_vec());
+        let signature: Bytes = self
+            .provider
+            .request("eth_sign", (self.address, message.clone()))
+            .await?;
+        let signature = Signature::try_from(signature.as_ref())?;
+        self.check_signer(signature.recover(message.as_ref())?)?;
+        Ok(signature)
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = tx.clone();
+        tx.set_from(self.address);
+        if tx.chain_id().is_none() {
+            tx.set_chain_id(self.chain_id);
+        }
+
+        let signed: SignedTransaction = self.provider.request("eth_signTransaction", [&tx]).await?;
+        let raw = match signed {
+            SignedTransaction::Raw(raw) | SignedTransaction::Object { raw } => raw,
+        };
+        let (signed_tx, signature) = TypedTransaction::decode_signed(&Rlp::new(&raw))?;
+
+        // The signature is attached to the transaction by the caller, so it must be for the same
+        // transaction.
+        if signed_tx.sighash() != tx.sighash() {
+            return Err(RemoteSignerError::TransactionMismatch);
+        }
+        // A signer with several accounts may sign with another one than the selected address.
+        self.check_signer(signature.recover(tx.sighash())?)?;
+        Ok(signature)
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        _payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        Err(RemoteSignerError::TypedDataUnsupported)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+/// Errors of the [WalletSigner].
+#[derive(Debug, Error)]
+pub enum WalletSignerError {
+    #[error(transparent)]
+    Local(#[from] WalletError),
+    #[error(transparent)]
+    Aws(#[from] AwsSignerError),
+    #[error(transparent)]
+    Remote(#[from] RemoteSignerError),
+}
+
+/// One of the supported signers, selected at runtime with [SignerArgs].
+#[derive(Debug, Clone)]
+pub enum WalletSigner {
+    Local(LocalWallet),
+    Aws(AwsSigner),
+    Remote(RemoteSigner),
+}
+
+#[async_trait]
+impl Signer for WalletSigner {
+    type Error = WalletSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        Ok(match self {
+            Self::Local(signer) => signer.sign_message(message).await?,
+            Self::Aws(signer) => signer.sign_message(message).await?,
+            Self::Remote(signer) => signer.sign_message(message).await?,
+        })
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        Ok(match self {
+            Self::Local(signer) => signer.sign_transaction(tx).await?,
+            Self::Aws(signer) => signer.sign_transaction(tx).await?,
+            Self::Remote(signer) => signer.sign_transaction(tx).await?,
+        })
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        Ok(match self {
+            Self::Local(signer) => signer.sign_typed_data(payload).await?,
+            Self::Aws(signer) => signer.sign_typed_data(payload).await?,
+            Self::Remote(signer) => signer.sign_typed_data(payload).await?,
+        })
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            Self::Local(signer) => signer.address(),
+            Self::Aws(signer) => signer.address(),
+            Self::Remote(signer) => signer.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            Self::Local(signer) => signer.chain_id(),
+            Self::Aws(signer) => signer.chain_id(),
+            Self::Remote(signer) => signer.chain_id(),
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            Self::Local(signer) => Self::Local(signer.with_chain_id(chain_id)),
+            Self::Aws(signer) => Self::Aws(signer.with_chain_id(chain_id)),
+            Self::Remote(signer) => Self::Remote(signer.with_chain_id(chain_id)),
+        }
+    }
+}
+
+/// Command line arguments selecting the wallet sending the transactions.
+///
+/// The options are mutually exclusive, in the `signer` group. They are optional for commands that
+/// can do without a wallet, such as `publisher submit --relayer`, so that [SignerArgs::signer]
+/// fails if none is given.
+///
+/// No option is read from the environment, so that a key left there is never used unnoticed.
+#[derive(Args, Debug, Clone)]
+#[clap(group(
+    ArgGroup::new("signer").args([
+        "keystore",
+        "aws_kms_key_id",
+        "remote_signer",
+        "insecure_private_key",
+    ])
+))]
+pub struct SignerArgs {
+    /// Encrypted JSON keystore of the wallet.
+    #[clap(long)]
+    pub keystore: Option<PathBuf>,
+
+    /// File containing the password of the keystore. The password is prompted for if not given.
+    #[clap(long, requires = "keystore")]
+    pub password_file: Option<PathBuf>,
+
+    /// ID, ARN or alias of the AWS KMS key of the wallet. The region and credentials are read from
+    /// the environment.
+    #[clap(long)]
+    pub aws_kms_key_id: Option<String>,
+
+    /// Endpoint of a remote signer, such as Clef or Web3Signer, signing with
+    /// `eth_signTransaction`.
+    #[clap(long)]
+    pub remote_signer: Option<String>,
+
+    /// Address of the wallet of the remote signer. Defaults to its first account.
+    #[clap(long, requires = "remote_signer")]
+    pub remote_signer_address: Option<Address>,
+
+    /// Hex encoded private key of the wallet, as the wallet key identifier of the Bonsai relay.
+    /// Only meant for development keys, such as the ones of anvil: the key is visible to other
+    /// users in `ps` and kept in the shell history.
+    #[clap(long)]
+    pub insecure_private_key: Option<WalletKey>,
+}
+
+impl SignerArgs {
+    /// Returns whether a wallet is selected.
+    pub fn is_selected(&self) -> bool {
+        self.keystore.is_some()
+            || self.aws_kms_key_id.is_some()
+            || self.remote_signer.is_some()
+            || self.insecure_private_key.is_some()
+    }
+
+    /// Returns the selected signer, signing for the given chain.
+    pub async fn signer(&self, chain_id: u64) -> Result<WalletSigner> {
+        let signer = if let Some(keystore) = &self.keystore {
+            WalletSigner::Local(decrypt_keystore(keystore, self.password_file.as_deref())?)
+        } else if let Some(key_id) = &self.aws_kms_key_id {
+            // The region is read from the environment, as for the AWS CLI.
+            let kms = KmsClient::new(Default::default());
+            WalletSigner::Aws(AwsSigner::new(kms, key_id, chain_id).await?)
+        } else if let Some(url) = &self.remote_signer {
+            let signer = RemoteSigner::connect(url, self.remote_signer_address, chain_id).await?;
+            WalletSigner::Remote(signer)
+        } else if let Some(wallet_key) = &self.insecure_private_key {
+            eprintln!(
+                "Warning: signing with the raw key of --insecure-private-key, which is only meant \
+                 for development keys; use --keystore, --aws-kms-key-id or --remote-signer instead"
+            );
+            WalletSigner::Local(LocalWallet::from(SigningKey::from(wallet_key.get_key())))
+        } else {
+            bail!("no wallet selected; pass --keystore, --aws-kms-key-id or --remote-signer")
+        };
+        log::info!("Signing with {:?}", signer.address());
+
+        Ok(signer.with_chain_id(chain_id))
+    }
+}
+
+/// Decrypts an encrypted JSON keystore with the password read from the given file, or prompted
+/// for if there is none.
+pub fn decrypt_keystore(keystore: &Path, password_file: Option<&Path>) -> Result<LocalWallet> {
+    let password = match password_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+        None => rpassword::prompt_password(format!("Password of {}: ", keystore.display()))?,
+    };
+    LocalWallet::decrypt_keystore(keystore, password)
+        .with_context(|| format!("failed to decrypt keystore {}", keystore.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_partial_json, method},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    fn wallet(key: u8) -> LocalWallet {
+        LocalWallet::from_bytes(&[key; 32])
+            .unwrap()
+            .with_chain_id(1u64)
+    }
+
+    fn transaction(nonce: u64) -> TypedTransaction {
+        Eip1559TransactionRequest::new()
+            .chain_id(1)
+            .nonce(nonce)
+            .to(Address::repeat_byte(1))
+            .gas(100_000)
+            .max_fee_per_gas(2_000_000_000u64)
+            .max_priority_fee_per_gas(1_000_000_000u64)
+            .into()
+    }
+
+    /// Returns the raw transaction signed by the wallet.
+    fn signed(wallet: &LocalWallet, tx: &TypedTransaction) -> Bytes {
+        tx.rlp_signed(&wallet.sign_transaction_sync(tx).unwrap())
+    }
+
+    /// Returns a remote signer of the address of the wallet, whose `eth_signTransaction`
+    /// responds with the given result.
+    async fn remote_signer(server: &MockServer, result: serde_json::Value) -> RemoteSigner {
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({ "method": "eth_signTransaction" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": result,
+            })))
+            .mount(server)
+            .await;
+        RemoteSigner {
+            provider: Provider::<Http>::try_from(server.uri()).unwrap(),
+            address: wallet(1).address(),
+            chain_id: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn sign_transaction_with_web3signer() {
+        let server = MockServer::start().await;
+        let tx = transaction(0);
+        // Web3Signer responds with the raw transaction.
+        let signer = remote_signer(&server, json!(signed(&wallet(1), &tx))).await;
+
+        let signature = signer.sign_transaction(&tx).await.unwrap();
+        assert_eq!(
+            signature.recover(tx.sighash()).unwrap(),
+            wallet(1).address()
+        );
+    }
+
+    #[tokio::test]
+    async fn sign_transaction_with_clef() {
+        let server = MockServer::start().await;
+        let tx = transaction(0);
+        // Clef responds with the raw transaction and the decoded one.
+        let result = json!({ "raw": signed(&wallet(1), &tx), "tx": {} });
+        let signer = remote_signer(&server, result).await;
+
+        let signature = signer.sign_transaction(&tx).await.unwrap();
+        assert_eq!(
+            signature.recover(tx.sighash()).unwrap(),
+            wallet(1).address()
+        );
+    }
+
+    #[tokio::test]
+    async fn sign_transaction_rejects_other_transaction() {
+        let server = MockServer::start().await;
+        let signer = remote_signer(&server, json!(signed(&wallet(1), &transaction(1)))).await;
+
+        let err = signer.sign_transaction(&transaction(0)).await.unwrap_err();
+        assert!(matches!(err, RemoteSignerError::TransactionMismatch));
+    }
+
+    #[tokio::test]
+    async fn sign_transaction_rejects_other_signer() {
+        let server = MockServer::start().await;
+        let tx = transaction(0);
+        let signer = remote_signer(&server, json!(signed(&wallet(2), &tx))).await;
+
+        let err = signer.sign_transaction(&tx).await.unwrap_err();
+        assert!(
+            matches!(err, RemoteSignerError::WrongSigner(address) if address == wallet(2).address())
+        );
+    }
+}
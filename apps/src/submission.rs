@@ -0,0 +1,248 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proofs submitted to `POAPGroup` or `POAPSetGroup`, either by the publisher or by a relayer.
+
+use alloy_primitives::{Bytes, B256};
+use alloy_sol_types::{sol, SolCall, SolInterface};
+use anyhow::{bail, ensure, Context, Result};
+use methods::{
+    COMPOSE_POAP_OWNER_ID, IS_POAP_OWNER_BATCH_ID, IS_POAP_OWNER_ID, IS_POAP_SET_MEMBER_ID,
+};
+use risc0_zkvm::{is_dev_mode, sha::Digest};
+use serde::{Deserialize, Serialize};
+use zkpoap_types::{BlockCommitment, ProofData};
+
+use crate::{
+    groth16,
+    journal::{BatchJournal, ComposedJournal, Journal, SetJournal},
+    Snark,
+};
+
+sol! {
+    interface IPOAPGroup {
+        function joinGroup(bytes memory journal, bytes32 post_state_digest, bytes calldata seal);
+        function joinGroupBatch(bytes memory journal, bytes32 post_state_digest, bytes calldata seal);
+        function joinGroupComposed(bytes memory journal, bytes32 post_state_digest, bytes calldata seal);
+    }
+}
+
+/// The guests whose proofs `POAPGroup` and `POAPSetGroup` accept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Guest {
+    /// `is_poap_owner`, submitted with `joinGroup`.
+    IsPoapOwner,
+    /// `is_poap_owner_batch`, submitted with `joinGroupBatch`.
+    IsPoapOwnerBatch,
+    /// `compose_poap_owner`, submitted with `joinGroupComposed`.
+    ComposePoapOwner,
+    /// `is_poap_set_member`, submitted with `joinGroup` of `POAPSetGroup`.
+    IsPoapSetMember,
+}
+
+impl Guest {
+    pub fn image_id(self) -> [u32; 8] {
+        match self {
+            Guest::IsPoapOwner => IS_POAP_OWNER_ID,
+            Guest::IsPoapOwnerBatch => IS_POAP_OWNER_BATCH_ID,
+            Guest::ComposePoapOwner => COMPOSE_POAP_OWNER_ID,
+            Guest::IsPoapSetMember => IS_POAP_SET_MEMBER_ID,
+        }
+    }
+
+    /// Returns the guest with the given hex encoded image ID, as in the state and proof files.
+    pub fn from_image_id(image_id: &str) -> Result<Self> {
+        [
+            Guest::IsPoapOwner,
+            Guest::IsPoapOwnerBatch,
+            Guest::ComposePoapOwner,
+            Guest::IsPoapSetMember,
+        ]
+        .into_iter()
+        .find(|guest| Digest::from(guest.image_id()).to_string() == image_id)
+        .with_context(|| {
+            format!(
+                "image ID {} is not of a guest accepted by POAPGroup",
+                image_id
+            )
+        })
+    }
+}
+
+/// A proof with the arguments of the `POAPGroup` function of its guest, as written by
+/// `publisher prove` and posted to a relayer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Submission {
+    /// Hex encoded image ID of the proven guest.
+    pub image_id: String,
+    pub journal: Bytes,
+    pub post_state_digest: B256,
+    pub seal: Bytes,
+}
+
+impl Submission {
+    pub fn new(guest: Guest, snark: Snark) -> Self {
+        let (journal, post_state_digest, seal) = snark;
+        Self {
+            image_id: Digest::from(guest.image_id()).to_string(),
+            journal: journal.into(),
+            post_state_digest,
+            seal: seal.into(),
+        }
+    }
+
+    pub fn guest(&self) -> Result<Guest> {
+        Guest::from_image_id(&self.image_id)
+    }
+
+    pub fn snark(&self) -> Snark {
+        (
+            self.journal.to_vec(),
+            self.post_state_digest,
+            self.seal.to_vec(),
+        )
+    }
+
+    /// Decodes the calldata of a `POAPGroup` function submitting a proof, e.g. of a mined
+    /// transaction. `joinGroup` is decoded as a proof of `is_poap_owner`.
+    pub fn from_calldata(calldata: &[u8]) -> Result<Self> {
+        let call = IPOAPGroup::IPOAPGroupCalls::abi_decode(calldata, true)
+            .context("calldata does not submit a proof to POAPGroup")?;
+        let (guest, journal, post_state_digest, seal) = match call {
+            IPOAPGroup::IPOAPGroupCalls::joinGroup(call) => (
+                Guest::IsPoapOwner,
+                call.journal,
+                call.post_state_digest,
+                call.seal,
+            ),
+            IPOAPGroup::IPOAPGroupCalls::joinGroupBatch(call) => (
+                Guest::IsPoapOwnerBatch,
+                call.journal,
+                call.post_state_digest,
+                call.seal,
+            ),
+            IPOAPGroup::IPOAPGroupCalls::joinGroupComposed(call) => (
+                Guest::ComposePoapOwner,
+                call.journal,
+                call.post_state_digest,
+                call.seal,
+            ),
+        };
+        Ok(Self {
+            image_id: Digest::from(guest.image_id()).to_string(),
+            journal,
+            post_state_digest,
+            seal,
+        })
+    }
+
+    /// Returns the proof data of the members added to the group by the proof.
+    pub fn proof_data(&self) -> Result<Vec<ProofData>> {
+        Ok(match self.guest()? {
+            Guest::IsPoapOwner => vec![Journal::decode(&self.journal)?.proof_data],
+            Guest::IsPoapOwnerBatch => BatchJournal::decode(&self.journal)?.proof_data,
+            Guest::ComposePoapOwner => {
+                vec![ComposedJournal::decode(&self.journal)?.journal.proof_data]
+            }
+            Guest::IsPoapSetMember => bail!("proofs of is_poap_set_member are not for POAPGroup"),
+        })
+    }
+
+    /// Returns the chain ID of the POAP contract the proof was made against, from its journal.
+    pub fn poap_chain_id(&self) -> Result<u64> {
+        let poap_chain_id = match self.guest()? {
+            Guest::IsPoapSetMember => SetJournal::decode(&self.journal)?.proof_data.poapChainId,
+            // The members of a batch are all proven against the same POAP contract.
+            _ => {
+                self.proof_data()?
+                    .first()
+                    .context("the proof adds no member")?
+                    .poapChainId
+            }
+        };
+        poap_chain_id
+            .try_into()
+            .context("invalid POAP chain ID in the journal")
+    }
+
+    /// Checks that the proof is of a guest accepted by `POAPGroup`, that its journal decodes and
+    /// that its Groth16 seal verifies.
+    pub fn verify(&self) -> Result<()> {
+        let guest = self.guest()?;
+        self.calldata()?;
+
+        // Proofs of the dev-mode prover have an empty seal, only accepted by
+        // `MockRiscZeroVerifier`.
+        if self.seal.is_empty() {
+            ensure!(
+                is_dev_mode(),
+                "the proof has no seal, which is only accepted in dev mode"
+            );
+            return Ok(());
+        }
+        groth16::verify(
+            &self.seal,
+            guest.image_id(),
+            self.post_state_digest,
+            &self.journal,
+        )
+    }
+
+    /// Returns the calldata submitting the proof to `POAPGroup`, or to `POAPSetGroup` for
+    /// [Guest::IsPoapSetMember], and the block the proof is for.
+    pub fn calldata(&self) -> Result<(BlockCommitment, Vec<u8>)> {
+        let journal = self.journal.clone();
+        let post_state_digest = self.post_state_digest;
+        let seal = self.seal.clone();
+        match self.guest()? {
+            Guest::IsPoapOwner => {
+                let block = Journal::decode(&journal)?.block;
+                let call = IPOAPGroup::joinGroupCall {
+                    journal,
+                    post_state_digest,
+                    seal,
+                };
+                Ok((block, call.abi_encode()))
+            }
+            Guest::IsPoapOwnerBatch => {
+                let block = BatchJournal::decode(&journal)?.block;
+                let call = IPOAPGroup::joinGroupBatchCall {
+                    journal,
+                    post_state_digest,
+                    seal,
+                };
+                Ok((block, call.abi_encode()))
+            }
+            Guest::ComposePoapOwner => {
+                let block = ComposedJournal::decode(&journal)?.journal.block;
+                let call = IPOAPGroup::joinGroupComposedCall {
+                    journal,
+                    post_state_digest,
+                    seal,
+                };
+                Ok((block, call.abi_encode()))
+            }
+            // `POAPSetGroup.joinGroup` has the same signature as `POAPGroup.joinGroup`.
+            Guest::IsPoapSetMember => {
+                let block = SetJournal::decode(&journal)?.block;
+                let call = IPOAPGroup::joinGroupCall {
+                    journal,
+                    post_state_digest,
+                    seal,
+                };
+                Ok((block, call.abi_encode()))
+            }
+        }
+    }
+}
@@ -0,0 +1,570 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sending transactions to `POAPGroup`, with EIP-1559 fees, replacement of stuck transactions and
+//! decoding of revert reasons.
+
+use std::time::Duration;
+
+use alloy_sol_types::{sol, Panic, Revert, SolError, SolInterface};
+use anyhow::{Context, Result};
+use clap::Args;
+use ethers::{prelude::*, types::transaction::eip2718::TypedTransaction, utils::parse_units};
+use thiserror::Error;
+
+sol! {
+    /// Custom errors of `ISemaphore.sol`.
+    interface ISemaphore {
+        error Semaphore__CallerIsNotTheGroupAdmin();
+        error Semaphore__MerkleTreeDepthIsNotSupported();
+        error Semaphore__MerkleTreeRootIsExpired();
+        error Semaphore__MerkleTreeRootIsNotPartOfTheGroup();
+        error Semaphore__YouAreUsingTheSameNillifierTwice();
+    }
+}
+
+/// A custom error of the Semaphore contract.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum SemaphoreError {
+    #[error("caller is not the group admin")]
+    CallerIsNotTheGroupAdmin,
+    #[error("Merkle tree depth is not supported")]
+    MerkleTreeDepthIsNotSupported,
+    #[error("Merkle tree root is expired")]
+    MerkleTreeRootIsExpired,
+    #[error("Merkle tree root is not part of the group")]
+    MerkleTreeRootIsNotPartOfTheGroup,
+    #[error("nullifier was already used")]
+    YouAreUsingTheSameNillifierTwice,
+}
+
+impl From<ISemaphore::ISemaphoreErrors> for SemaphoreError {
+    fn from(err: ISemaphore::ISemaphoreErrors) -> Self {
+        use ISemaphore::ISemaphoreErrors as E;
+        match err {
+            E::Semaphore__CallerIsNotTheGroupAdmin(_) => Self::CallerIsNotTheGroupAdmin,
+            E::Semaphore__MerkleTreeDepthIsNotSupported(_) => Self::MerkleTreeDepthIsNotSupported,
+            E::Semaphore__MerkleTreeRootIsExpired(_) => Self::MerkleTreeRootIsExpired,
+            E::Semaphore__MerkleTreeRootIsNotPartOfTheGroup(_) => {
+                Self::MerkleTreeRootIsNotPartOfTheGroup
+            }
+            E::Semaphore__YouAreUsingTheSameNillifierTwice(_) => {
+                Self::YouAreUsingTheSameNillifierTwice
+            }
+        }
+    }
+}
+
+/// A `require` of `POAPGroup` or `POAPSetGroup` that failed.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum GroupError {
+    #[error("the nullifier of the proof was already used to join the group")]
+    DuplicateProof,
+    #[error("the proof is for another event")]
+    InvalidEventId,
+    #[error("the proof is for another chain")]
+    InvalidChainId,
+    #[error("the proof is for another group")]
+    InvalidGroup,
+    #[error("the proof is for another nullifier mode")]
+    InvalidNullifierMode,
+    #[error("the proof is for another POAP contract")]
+    InvalidPoapContract,
+    #[error("the proof is for another POAP chain")]
+    InvalidPoapChainId,
+    #[error("the hash of the proven block does not match")]
+    InvalidBlock,
+    #[error("the proven block is too old")]
+    BlockTooOld,
+    #[error("the POAP was not held long enough")]
+    HeldTooRecently,
+    #[error("the composed ownership proof is of another guest")]
+    InvalidOwnershipImageId,
+    #[error("the composed signature proof is of another guest")]
+    InvalidSignatureImageId,
+    #[error("the proof is for another event set")]
+    InvalidEventSet,
+    #[error("the proof is for another threshold")]
+    InvalidThreshold,
+    #[error("the POAPs are from fewer events of the set than the threshold")]
+    ThresholdNotMet,
+}
+
+impl GroupError {
+    /// Returns the error of the given `require` message.
+    pub fn from_reason(reason: &str) -> Option<Self> {
+        let err = match reason {
+            "DUPLICATE_PROOF" => Self::DuplicateProof,
+            "INVALID_EVENT_ID" => Self::InvalidEventId,
+            "INVALID_CHAIN_ID" => Self::InvalidChainId,
+            "INVALID_GROUP" => Self::InvalidGroup,
+            "INVALID_NULLIFIER_MODE" => Self::InvalidNullifierMode,
+            "INVALID_POAP_CONTRACT" => Self::InvalidPoapContract,
+            "INVALID_POAP_CHAIN_ID" => Self::InvalidPoapChainId,
+            "INVALID_BLOCK" => Self::InvalidBlock,
+            "BLOCK_TOO_OLD" => Self::BlockTooOld,
+            "HELD_TOO_RECENTLY" => Self::HeldTooRecently,
+            "INVALID_OWNERSHIP_IMAGE_ID" => Self::InvalidOwnershipImageId,
+            "INVALID_SIGNATURE_IMAGE_ID" => Self::InvalidSignatureImageId,
+            "INVALID_EVENT_SET" => Self::InvalidEventSet,
+            "INVALID_THRESHOLD" => Self::InvalidThreshold,
+            "THRESHOLD_NOT_MET" => Self::ThresholdNotMet,
+            _ => return None,
+        };
+        Some(err)
+    }
+}
+
+/// The decoded revert data of a call.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    #[error("POAPGroup: {0}")]
+    Group(GroupError),
+    #[error("Semaphore: {0}")]
+    Semaphore(SemaphoreError),
+    #[error("{0}")]
+    Reason(String),
+    #[error("panic with code {0}")]
+    Panic(alloy_primitives::U256),
+    /// A `require` without message, e.g. of the proof verification.
+    #[error("reverted without reason, e.g. because the proof did not verify")]
+    Empty,
+    #[error("unknown revert data 0x{}", hex::encode(.0))]
+    Unknown(Vec<u8>),
+}
+
+impl RevertReason {
+    /// Decodes the revert data of `Error(string)`, `Panic(uint256)` and the Semaphore errors.
+    pub fn decode(data: &[u8]) -> Self {
+        if data.is_empty() {
+            return Self::Empty;
+        }
+        if let Ok(revert) = Revert::abi_decode(data, true) {
+            return match GroupError::from_reason(&revert.reason) {
+                Some(err) => Self::Group(err),
+                None => Self::Reason(revert.reason),
+            };
+        }
+        if let Ok(panic) = Panic::abi_decode(data, true) {
+            return Self::Panic(panic.code);
+        }
+        match ISemaphore::ISemaphoreErrors::abi_decode(data, true) {
+            Ok(err) => Self::Semaphore(err.into()),
+            Err(_) => Self::Unknown(data.to_vec()),
+        }
+    }
+
+    fn describe(reason: &Option<Self>) -> String {
+        match reason {
+            Some(reason) => reason.to_string(),
+            None => "unknown reason".to_string(),
+        }
+    }
+
+    /// Returns the revert reason of a failed RPC call, or `None` if it did not revert.
+    fn from_middleware_error(err: &impl MiddlewareError) -> Option<Self> {
+        let data = err.as_error_response()?.as_revert_data()?;
+        Some(Self::decode(&data))
+    }
+}
+
+/// Errors of a transaction sent by [TxSender].
+#[derive(Debug, Error)]
+pub enum TxError {
+    #[error("transaction would revert: {0}")]
+    WouldRevert(RevertReason),
+    #[error("transaction {hash:?} reverted: {}", RevertReason::describe(.reason))]
+    Reverted {
+        hash: H256,
+        reason: Option<RevertReason>,
+    },
+    #[error("transaction was not mined after {0} attempts")]
+    NotMined(u32),
+}
+
+/// Gas and fee settings of [TxSender].
+#[derive(Clone, Debug)]
+pub struct FeeConfig {
+    /// Cap of the max fee per gas, in wei.
+    pub max_fee_per_gas: Option<U256>,
+    /// Cap of the max priority fee per gas, in wei.
+    pub max_priority_fee_per_gas: Option<U256>,
+    /// Percentage added to the estimated gas limit.
+    pub gas_margin: u64,
+    /// Percentage by which the fees of a stuck transaction are raised when it is replaced.
+    pub fee_bump: u64,
+    /// Time after which a pending transaction is considered stuck.
+    pub stuck_timeout: Duration,
+    /// Number of times a transaction is sent before giving up.
+    pub max_attempts: u32,
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        Self {
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            gas_margin: 20,
+            fee_bump: 20,
+            stuck_timeout: Duration::from_secs(120),
+            max_attempts: 5,
+        }
+    }
+}
+
+impl FeeConfig {
+    /// Applies the configured caps to the fees.
+    fn cap(&self, max_fee: U256, max_priority_fee: U256) -> (U256, U256) {
+        let max_fee = match self.max_fee_per_gas {
+            Some(cap) => max_fee.min(cap),
+            None => max_fee,
+        };
+        let max_priority_fee = match self.max_priority_fee_per_gas {
+            Some(cap) => max_priority_fee.min(cap),
+            None => max_priority_fee,
+        };
+        (max_fee, max_priority_fee.min(max_fee))
+    }
+
+    /// Returns the fees of a replacement of a stuck transaction, raised by the fee bump and
+    /// capped, or `None` if the caps leave either fee unchanged.
+    ///
+    /// Replacements must raise both fees, by at least 10% for most nodes.
+    fn bump(&self, max_fee: U256, max_priority_fee: U256) -> Option<(U256, U256)> {
+        let (bumped_max_fee, bumped_max_priority_fee) = self.cap(
+            max_fee * (100 + self.fee_bump) / 100,
+            max_priority_fee * (100 + self.fee_bump) / 100,
+        );
+        (bumped_max_fee > max_fee && bumped_max_priority_fee > max_priority_fee)
+            .then_some((bumped_max_fee, bumped_max_priority_fee))
+    }
+}
+
+/// Command line arguments configuring the fees of transactions.
+#[derive(Args, Debug, Clone)]
+pub struct FeeArgs {
+    /// Cap of the max fee per gas in gwei.
+    #[clap(long)]
+    pub max_fee_per_gas: Option<String>,
+
+    /// Cap of the max priority fee per gas in gwei.
+    #[clap(long)]
+    pub max_priority_fee_per_gas: Option<String>,
+
+    /// Percentage added to the estimated gas limit.
+    #[clap(long, default_value_t = 20)]
+    pub gas_margin: u64,
+
+    /// Percentage by which the fees of a stuck transaction are raised when it is replaced.
+    #[clap(long, default_value_t = 20)]
+    pub fee_bump: u64,
+
+    /// Seconds after which a pending transaction is replaced with higher fees.
+    #[clap(long, default_value_t = 120)]
+    pub stuck_timeout: u64,
+
+    /// Number of times a transaction is sent before giving up.
+    #[clap(long, default_value_t = 5)]
+    pub max_attempts: u32,
+}
+
+impl TryFrom<FeeArgs> for FeeConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(args: FeeArgs) -> Result<Self> {
+        let gwei = |value: Option<String>| -> Result<Option<U256>> {
+            value
+                .map(|value| -> Result<U256> { Ok(parse_units(&value, "gwei")?.into()) })
+                .transpose()
+        };
+        Ok(Self {
+            max_fee_per_gas: gwei(args.max_fee_per_gas)?,
+            max_priority_fee_per_gas: gwei(args.max_priority_fee_per_gas)?,
+            gas_margin: args.gas_margin,
+            fee_bump: args.fee_bump,
+            stuck_timeout: Duration::from_secs(args.stuck_timeout),
+            max_attempts: args.max_attempts,
+        })
+    }
+}
+
+/// Wrapper of a `SignerMiddleware` client to send transactions to the given
+/// contract's `Address`, signed by any ethers `Signer`.
+pub struct TxSender<S = LocalWallet> {
+    chain_id: u64,
+    client: SignerMiddleware<Provider<Http>, S>,
+    contract: Address,
+    fees: FeeConfig,
+}
+
+impl TxSender {
+    /// Creates a new `TxSender` signing with the given hex encoded private key.
+    pub fn new(chain_id: u64, rpc_url: &str, private_key: &str, contract: &str) -> Result<Self> {
+        let wallet = private_key.parse::<LocalWallet>()?;
+        Self::with_signer(chain_id, rpc_url, wallet, contract)
+    }
+}
+
+impl<S: Signer + 'static> TxSender<S> {
+    /// Creates a new `TxSender` signing with the given signer, e.g. a
+    /// [WalletSigner](crate::signer::WalletSigner).
+    pub fn with_signer(chain_id: u64, rpc_url: &str, signer: S, contract: &str) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(rpc_url)?;
+        let client = SignerMiddleware::new(provider, signer.with_chain_id(chain_id));
+        let contract = contract.parse::<Address>()?;
+
+        Ok(TxSender {
+            chain_id,
+            client,
+            contract,
+            fees: FeeConfig::default(),
+        })
+    }
+
+    /// Sets the gas and fee settings of the transactions.
+    pub fn with_fees(mut self, fees: FeeConfig) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Send a transaction with the given calldata.
+    ///
+    /// The transaction is sent with an estimated gas limit and EIP-1559 fees, and replaced with
+    /// higher fees while it is not mined. Returns a [TxError] if it would revert or reverted.
+    pub async fn send(&self, calldata: Vec<u8>) -> Result<TransactionReceipt> {
+        let mut tx = self.prepare(calldata).await?;
+        tx.set_nonce(self.pending_nonce().await?);
+        let hash = self.broadcast(&tx).await?;
+        self.confirm(tx, hash).await
+    }
+
+    /// Returns a transaction with the given calldata, an estimated gas limit and EIP-1559 fees,
+    /// and without nonce. Returns [TxError::WouldRevert] if it would revert.
+    pub async fn prepare(&self, calldata: Vec<u8>) -> Result<TypedTransaction> {
+        let mut tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .chain_id(self.chain_id)
+            .to(self.contract)
+            .from(self.client.address())
+            .data(calldata)
+            .into();
+
+        // A call that would revert fails the estimation, without paying for the transaction.
+        let gas = match self.client.estimate_gas(&tx, None).await {
+            Ok(gas) => gas,
+            Err(err) => match RevertReason::from_middleware_error(&err) {
+                Some(reason) => return Err(TxError::WouldRevert(reason).into()),
+                None => return Err(err).context("failed to estimate gas"),
+            },
+        };
+        tx.set_gas(gas * (100 + self.fees.gas_margin) / 100);
+
+        let (max_fee, max_priority_fee) = self.client.estimate_eip1559_fees(None).await?;
+        let (max_fee, max_priority_fee) = self.fees.cap(max_fee, max_priority_fee);
+        set_fees(&mut tx, max_fee, max_priority_fee);
+
+        Ok(tx)
+    }
+
+    /// Returns the nonce of the next transaction of the wallet, counting pending ones.
+    pub async fn pending_nonce(&self) -> Result<U256> {
+        Ok(self
+            .client
+            .get_transaction_count(self.client.address(), Some(BlockNumber::Pending.into()))
+            .await?)
+    }
+
+    /// Sends the prepared transaction with the nonce it was given, and returns its hash without
+    /// waiting for it to be mined.
+    pub async fn broadcast(&self, tx: &TypedTransaction) -> Result<H256> {
+        log::info!("Transaction request: {:?}", tx);
+        let pending = self
+            .client
+            .send_transaction(tx.clone(), None)
+            .await
+            .context("failed to send transaction")?;
+        Ok(pending.tx_hash())
+    }
+
+    /// Waits for the transaction sent as the given hash to be mined, replacing it with higher fees
+    /// and the same nonce while it is stuck. Returns a [TxError] if it reverted.
+    pub async fn confirm(
+        &self,
+        mut tx: TypedTransaction,
+        hash: H256,
+    ) -> Result<TransactionReceipt> {
+        let mut hashes = vec![hash];
+        for attempt in 1..=self.fees.max_attempts {
+            let last = hashes[hashes.len() - 1];
+            let pending = PendingTransaction::new(last, self.client.provider());
+            if let Ok(receipt) = tokio::time::timeout(self.fees.stuck_timeout, pending).await {
+                if let Some(receipt) = receipt? {
+                    return self.check_status(&tx, receipt).await;
+                }
+            }
+            if let Some(receipt) = self.mined_receipt(&hashes).await? {
+                return self.check_status(&tx, receipt).await;
+            }
+            if attempt == self.fees.max_attempts {
+                break;
+            }
+
+            // A replacement with the same fees would be rejected as underpriced, so once the fees
+            // reach their caps the sent transactions are only waited for.
+            let (max_fee, max_priority_fee) = fees(&tx);
+            let (max_fee, max_priority_fee) = match self.fees.bump(max_fee, max_priority_fee) {
+                Some(fees) => fees,
+                None => {
+                    log::warn!(
+                        "Transaction {:?} not mined after {:?}, fees are at their caps",
+                        hashes.last(),
+                        self.fees.stuck_timeout
+                    );
+                    continue;
+                }
+            };
+            log::warn!(
+                "Transaction {:?} not mined after {:?}, raising fees by {}%",
+                hashes.last(),
+                self.fees.stuck_timeout,
+                self.fees.fee_bump
+            );
+            set_fees(&mut tx, max_fee, max_priority_fee);
+            log::info!("Transaction request (attempt {}): {:?}", attempt + 1, &tx);
+
+            match self.client.send_transaction(tx.clone(), None).await {
+                Ok(pending) => hashes.push(pending.tx_hash()),
+                // The nonce is taken if a previous attempt was mined in the meantime.
+                Err(err) => match self.mined_receipt(&hashes).await? {
+                    Some(receipt) => return self.check_status(&tx, receipt).await,
+                    None => return Err(err).context("failed to send transaction"),
+                },
+            }
+        }
+
+        Err(TxError::NotMined(self.fees.max_attempts).into())
+    }
+
+    /// Returns the receipt of the first of the given transactions that was mined.
+    async fn mined_receipt(&self, hashes: &[H256]) -> Result<Option<TransactionReceipt>> {
+        for hash in hashes {
+            if let Some(receipt) = self.client.get_transaction_receipt(*hash).await? {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the receipt if the transaction succeeded, or its revert reason, obtained by
+    /// replaying it at its block.
+    async fn check_status(
+        &self,
+        tx: &TypedTransaction,
+        receipt: TransactionReceipt,
+    ) -> Result<TransactionReceipt> {
+        log::info!("Transaction receipt: {:?}", &receipt);
+        if receipt.status == Some(1.into()) {
+            return Ok(receipt);
+        }
+
+        let block = receipt.block_number.map(|number| number.into());
+        let reason = match self.client.call(tx, block).await {
+            Ok(_) => None,
+            Err(err) => RevertReason::from_middleware_error(&err),
+        };
+        Err(TxError::Reverted {
+            hash: receipt.transaction_hash,
+            reason,
+        }
+        .into())
+    }
+}
+
+/// Returns the max fee and the max priority fee per gas of an EIP-1559 transaction.
+fn fees(tx: &TypedTransaction) -> (U256, U256) {
+    match tx {
+        TypedTransaction::Eip1559(request) => (
+            request.max_fee_per_gas.unwrap_or_default(),
+            request.max_priority_fee_per_gas.unwrap_or_default(),
+        ),
+        _ => (U256::zero(), U256::zero()),
+    }
+}
+
+/// Sets the max fee and the max priority fee per gas of an EIP-1559 transaction.
+fn set_fees(tx: &mut TypedTransaction, max_fee: U256, max_priority_fee: U256) {
+    if let TypedTransaction::Eip1559(request) = tx {
+        request.max_fee_per_gas = Some(max_fee);
+        request.max_priority_fee_per_gas = Some(max_priority_fee);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_revert_reason() {
+        let data = Revert::from("DUPLICATE_PROOF").abi_encode();
+        assert_eq!(
+            RevertReason::decode(&data),
+            RevertReason::Group(GroupError::DuplicateProof)
+        );
+
+        let data = Revert::from("Ownable: caller is not the owner").abi_encode();
+        assert_eq!(
+            RevertReason::decode(&data),
+            RevertReason::Reason("Ownable: caller is not the owner".to_string())
+        );
+
+        let data = ISemaphore::Semaphore__MerkleTreeRootIsExpired {}.abi_encode();
+        assert_eq!(
+            RevertReason::decode(&data),
+            RevertReason::Semaphore(SemaphoreError::MerkleTreeRootIsExpired)
+        );
+
+        let data = Revert::from("THRESHOLD_NOT_MET").abi_encode();
+        assert_eq!(
+            RevertReason::decode(&data),
+            RevertReason::Group(GroupError::ThresholdNotMet)
+        );
+
+        assert_eq!(RevertReason::decode(&[]), RevertReason::Empty);
+        assert_eq!(
+            RevertReason::decode(&[1, 2, 3, 4]),
+            RevertReason::Unknown(vec![1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn bump_fees() {
+        let gwei = |value: u64| U256::from(value) * U256::exp10(9);
+        let fees = FeeConfig {
+            max_fee_per_gas: Some(gwei(120)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            fees.bump(gwei(50), gwei(2)),
+            Some((gwei(60), gwei(2) * 12 / 10))
+        );
+        // Only the max fee is capped, and still raised.
+        assert_eq!(
+            fees.bump(gwei(110), gwei(2)),
+            Some((gwei(120), gwei(2) * 12 / 10))
+        );
+        // Both fees must be raised for the replacement to be accepted.
+        assert_eq!(fees.bump(gwei(120), gwei(2)), None);
+        assert_eq!(fees.bump(gwei(50), U256::zero()), None);
+    }
+}
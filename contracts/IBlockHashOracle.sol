@@ -0,0 +1,11 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.20;
+
+/// @title Hashes of blocks of other chains, e.g. relayed by a bridge or checked by a light client.
+interface IBlockHashOracle {
+    /// @dev Returns the hash of a block of another chain.
+    /// @param chainId: Chain ID of the chain of the block.
+    /// @param blockNumber: Number of the block.
+    /// @return The hash of the block, or zero if it is unknown to the oracle.
+    function blockHash(uint256 chainId, uint256 blockNumber) external view returns (bytes32);
+}
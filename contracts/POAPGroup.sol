@@ -2,27 +2,56 @@
 pragma solidity ^0.8.20;
 
 import {IRiscZeroVerifier} from "risc0/IRiscZeroVerifier.sol";
+import {IBlockHashOracle} from "./IBlockHashOracle.sol";
 import {ImageID} from "./ImageID.sol"; 
 import {ISemaphore} from "./ISemaphore.sol";
 
 contract POAPGroup {
     IRiscZeroVerifier public immutable verifier;
     bytes32 public constant imageId = ImageID.IS_POAP_OWNER_ID;
+    bytes32 public constant batchImageId = ImageID.IS_POAP_OWNER_BATCH_ID;
+    bytes32 public constant composedImageId = ImageID.COMPOSE_POAP_OWNER_ID;
+    bytes32 public constant ownershipImageId = ImageID.POAP_OWNERSHIP_ID;
+    bytes32 public constant signatureImageId = ImageID.OWNER_SIGNATURE_ID;
 
     ISemaphore public immutable semaphore;
     uint256 public immutable eventId;
+    address public immutable poapContract;
+    uint256 public immutable poapChainId;
+    /// Members must have held their POAP since this block of the POAP chain, or earlier.
+    uint256 public immutable heldSinceBlock;
     uint256 public immutable groupId;
+    NullifierMode public immutable nullifierMode;
+    /// Provides the hashes of blocks of the POAP chain, if it is not this chain.
+    IBlockHashOracle public immutable blockHashOracle;
+    /// Maximum age of the committed block when its hash cannot be checked with `blockhash`.
+    uint256 public constant MAX_BLOCK_AGE = 1 hours;
     mapping(bytes32 => bool) public nullifires;
 
+    /// What the nullifier of a membership is keyed on. Both nullifiers are computable from public
+    /// data, so the address or POAP of every member is public; only signals are anonymous.
+    enum NullifierMode {
+        /// One membership per owner address.
+        Address,
+        /// One membership per POAP token, regardless of transfers.
+        Token
+    }
+
     struct BlockCommitment {
         bytes32 blockHash;
         uint256 blockNumber;
+        uint256 blockTimestamp;
     }
 
     struct ProofData {
         uint256 eventId;
         uint256 semaphoreId;
         bytes32 nullifier;
+        uint256 chainId;
+        address groupContract;
+        uint8 nullifierMode;
+        address poapContract;
+        uint256 poapChainId;
     }
 
     struct Signal {
@@ -36,11 +65,26 @@ contract POAPGroup {
     constructor(
         IRiscZeroVerifier _verifier,
         address _semaphore,
-        uint256 _eventId
+        uint256 _eventId,
+        NullifierMode _nullifierMode,
+        address _poapContract,
+        uint256 _poapChainId,
+        uint256 _heldSinceBlock,
+        IBlockHashOracle _blockHashOracle
     ) {
+        // Without an oracle, blocks of another chain cannot be checked; see `validateBlock`.
+        require(
+            _poapChainId == block.chainid || address(_blockHashOracle) != address(0),
+            "NO_BLOCK_HASH_ORACLE"
+        );
         verifier = _verifier;
         semaphore = ISemaphore(_semaphore);
         eventId = _eventId;
+        poapContract = _poapContract;
+        poapChainId = _poapChainId;
+        heldSinceBlock = _heldSinceBlock;
+        nullifierMode = _nullifierMode;
+        blockHashOracle = _blockHashOracle;
         groupId = uint256(keccak256(abi.encode(address(this), _eventId)));
         semaphore.createGroup(groupId, 20, address(this));
     }
@@ -55,16 +99,104 @@ contract POAPGroup {
         );
         (
             ProofData memory proofData,
+            BlockCommitment memory blockCommitment,
+            BlockCommitment memory heldSince
+        ) = abi.decode(journal, (ProofData, BlockCommitment, BlockCommitment));
+
+        require(heldSince.blockNumber <= heldSinceBlock, "HELD_TOO_RECENTLY");
+        validateBlock(blockCommitment);
+        addMember(proofData);
+    }
+
+    /// Adds several members with a single proof of the batch guest, which proves ownership of
+    /// all POAPs at the same block. Entries whose nullifier is already used, e.g. by a member who
+    /// joined while the batch was proven, are skipped instead of reverting the whole batch.
+    function joinGroupBatch(
+        bytes memory journal,
+        bytes32 postStateDigest,
+        bytes calldata seal
+    ) public {
+        require(
+            verifier.verify(seal, batchImageId, postStateDigest, sha256(journal))
+        );
+        (
+            ProofData[] memory proofData,
             BlockCommitment memory blockCommitment
-        ) = abi.decode(journal, (ProofData, BlockCommitment));
+        ) = abi.decode(journal, (ProofData[], BlockCommitment));
 
+        // The batch guest only proves ownership at the committed block.
+        require(blockCommitment.blockNumber <= heldSinceBlock, "HELD_TOO_RECENTLY");
+        validateBlock(blockCommitment);
+        for (uint256 i = 0; i < proofData.length; i++) {
+            if (nullifires[proofData[i].nullifier]) {
+                continue;
+            }
+            addMember(proofData[i]);
+        }
+    }
+
+    /// Adds a member with a proof of the guest composing the receipts of the ownership and the
+    /// signature guests, which proves ownership at the committed block.
+    function joinGroupComposed(
+        bytes memory journal,
+        bytes32 postStateDigest,
+        bytes calldata seal
+    ) public {
+        require(
+            verifier.verify(seal, composedImageId, postStateDigest, sha256(journal))
+        );
+        (
+            ProofData memory proofData,
+            BlockCommitment memory blockCommitment,
+            ,
+            bytes32 ownershipId,
+            bytes32 signatureId
+        ) = abi.decode(journal, (ProofData, BlockCommitment, BlockCommitment, bytes32, bytes32));
+
+        // The composing guest takes the image IDs as input, so check which guests it verified.
+        require(ownershipId == ownershipImageId, "INVALID_OWNERSHIP_IMAGE_ID");
+        require(signatureId == signatureImageId, "INVALID_SIGNATURE_IMAGE_ID");
+        require(blockCommitment.blockNumber <= heldSinceBlock, "HELD_TOO_RECENTLY");
+        validateBlock(blockCommitment);
+        addMember(proofData);
+    }
+
+    function addMember(ProofData memory proofData) internal {
         require(nullifires[proofData.nullifier] == false, "DUPLICATE_PROOF");
-        // require(proofData.eventId == eventId, "INVALID_EVENT_ID");
+        require(proofData.eventId == eventId, "INVALID_EVENT_ID");
+        require(proofData.chainId == block.chainid, "INVALID_CHAIN_ID");
+        require(proofData.groupContract == address(this), "INVALID_GROUP");
+        require(proofData.nullifierMode == uint8(nullifierMode), "INVALID_NULLIFIER_MODE");
+        require(proofData.poapContract == poapContract, "INVALID_POAP_CONTRACT");
+        require(proofData.poapChainId == poapChainId, "INVALID_POAP_CHAIN_ID");
 
         nullifires[proofData.nullifier] = true;
         semaphore.addMember(groupId, proofData.semaphoreId);
     }
 
+    /// Rejects proofs of stale or unknown state. Blocks of this chain must be one of the last 256
+    /// blocks, blocks of another chain must be known to `blockHashOracle` and not be older than
+    /// `MAX_BLOCK_AGE`.
+    function validateBlock(BlockCommitment memory commitment) internal view {
+        if (poapChainId == block.chainid) {
+            require(
+                blockhash(commitment.blockNumber) == commitment.blockHash,
+                "INVALID_BLOCK"
+            );
+        } else {
+            // The header is chosen by the prover, so its hash must be anchored to the POAP chain.
+            bytes32 blockHash = blockHashOracle.blockHash(poapChainId, commitment.blockNumber);
+            require(
+                blockHash != bytes32(0) && blockHash == commitment.blockHash,
+                "INVALID_BLOCK"
+            );
+            require(
+                commitment.blockTimestamp + MAX_BLOCK_AGE >= block.timestamp,
+                "BLOCK_TOO_OLD"
+            );
+        }
+    }
+
     function validateSignal(Signal memory signal) external {
         ISemaphore(semaphore).verifyProof(
             groupId,
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.20;
+
+import {IRiscZeroVerifier} from "risc0/IRiscZeroVerifier.sol";
+import {IBlockHashOracle} from "./IBlockHashOracle.sol";
+import {ImageID} from "./ImageID.sol";
+import {ISemaphore} from "./ISemaphore.sol";
+
+/// A Semaphore group of holders of POAPs from at least `threshold` events of a set.
+/// Only the commitment `keccak256(abi.encode(eventIds))` to the sorted event IDs is stored.
+contract POAPSetGroup {
+    IRiscZeroVerifier public immutable verifier;
+    bytes32 public constant imageId = ImageID.IS_POAP_SET_MEMBER_ID;
+
+    ISemaphore public immutable semaphore;
+    bytes32 public immutable eventSetCommitment;
+    uint256 public immutable threshold;
+    address public immutable poapContract;
+    uint256 public immutable poapChainId;
+    uint256 public immutable groupId;
+    /// Provides the hashes of blocks of the POAP chain, if it is not this chain.
+    IBlockHashOracle public immutable blockHashOracle;
+    /// Maximum age of the committed block when its hash cannot be checked with `blockhash`.
+    uint256 public constant MAX_BLOCK_AGE = 1 hours;
+    mapping(bytes32 => bool) public nullifiers;
+
+    struct BlockCommitment {
+        bytes32 blockHash;
+        uint256 blockNumber;
+        uint256 blockTimestamp;
+    }
+
+    struct SetProofData {
+        bytes32 eventSetCommitment;
+        uint256 threshold;
+        bool thresholdMet;
+        uint256 semaphoreId;
+        bytes32 nullifier;
+        uint256 chainId;
+        address groupContract;
+        address poapContract;
+        uint256 poapChainId;
+    }
+
+    constructor(
+        IRiscZeroVerifier _verifier,
+        address _semaphore,
+        bytes32 _eventSetCommitment,
+        uint256 _threshold,
+        address _poapContract,
+        uint256 _poapChainId,
+        IBlockHashOracle _blockHashOracle
+    ) {
+        // Without an oracle, blocks of another chain cannot be checked; see `validateBlock`.
+        require(
+            _poapChainId == block.chainid || address(_blockHashOracle) != address(0),
+            "NO_BLOCK_HASH_ORACLE"
+        );
+        verifier = _verifier;
+        semaphore = ISemaphore(_semaphore);
+        eventSetCommitment = _eventSetCommitment;
+        threshold = _threshold;
+        poapContract = _poapContract;
+        poapChainId = _poapChainId;
+        blockHashOracle = _blockHashOracle;
+        groupId = uint256(keccak256(abi.encode(address(this), _eventSetCommitment)));
+        semaphore.createGroup(groupId, 20, address(this));
+    }
+
+    function joinGroup(
+        bytes memory journal,
+        bytes32 postStateDigest,
+        bytes calldata seal
+    ) public {
+        require(
+            verifier.verify(seal, imageId, postStateDigest, sha256(journal))
+        );
+        (
+            SetProofData memory proofData,
+            BlockCommitment memory blockCommitment
+        ) = abi.decode(journal, (SetProofData, BlockCommitment));
+
+        require(nullifiers[proofData.nullifier] == false, "DUPLICATE_PROOF");
+        require(proofData.eventSetCommitment == eventSetCommitment, "INVALID_EVENT_SET");
+        require(proofData.threshold == threshold, "INVALID_THRESHOLD");
+        require(proofData.thresholdMet, "THRESHOLD_NOT_MET");
+        require(proofData.chainId == block.chainid, "INVALID_CHAIN_ID");
+        require(proofData.groupContract == address(this), "INVALID_GROUP");
+        require(proofData.poapContract == poapContract, "INVALID_POAP_CONTRACT");
+        require(proofData.poapChainId == poapChainId, "INVALID_POAP_CHAIN_ID");
+        validateBlock(blockCommitment);
+
+        nullifiers[proofData.nullifier] = true;
+        semaphore.addMember(groupId, proofData.semaphoreId);
+    }
+
+    /// Rejects proofs of stale or unknown state. Blocks of this chain must be one of the last 256
+    /// blocks, blocks of another chain must be known to `blockHashOracle` and not be older than
+    /// `MAX_BLOCK_AGE`.
+    function validateBlock(BlockCommitment memory commitment) internal view {
+        if (poapChainId == block.chainid) {
+            require(
+                blockhash(commitment.blockNumber) == commitment.blockHash,
+                "INVALID_BLOCK"
+            );
+        } else {
+            // The header is chosen by the prover, so its hash must be anchored to the POAP chain.
+            bytes32 blockHash = blockHashOracle.blockHash(poapChainId, commitment.blockNumber);
+            require(
+                blockHash != bytes32(0) && blockHash == commitment.blockHash,
+                "INVALID_BLOCK"
+            );
+            require(
+                commitment.blockTimestamp + MAX_BLOCK_AGE >= block.timestamp,
+                "BLOCK_TOO_OLD"
+            );
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.20;
+
+import {IBlockHashOracle} from "./IBlockHashOracle.sol";
+
+/// @title Block hashes of other chains posted by a trusted poster.
+/// @dev The groups using this oracle trust the poster not to post the hash of a fabricated block,
+/// so it is only meant for testnets and demos; use a bridge or a light client in production. A
+/// posted hash cannot be replaced, so a compromised poster cannot rewrite the blocks that members
+/// already joined with.
+contract TrustedBlockHashOracle is IBlockHashOracle {
+    /// The only address allowed to post block hashes, i.e. the deployer.
+    address public immutable poster;
+    mapping(uint256 => mapping(uint256 => bytes32)) internal blockHashes;
+
+    event BlockHashPosted(uint256 indexed chainId, uint256 indexed blockNumber, bytes32 blockHash);
+
+    constructor() {
+        poster = msg.sender;
+    }
+
+    /// @dev Posts the hash of a block of another chain, e.g. as returned by
+    /// `cast block <blockNumber> --field hash` on an RPC node of that chain.
+    function postBlockHash(uint256 chainId, uint256 blockNumber, bytes32 blockHash_) external {
+        require(msg.sender == poster, "NOT_POSTER");
+        require(blockHash_ != bytes32(0), "ZERO_BLOCK_HASH");
+        require(blockHashes[chainId][blockNumber] == bytes32(0), "BLOCK_HASH_ALREADY_POSTED");
+        blockHashes[chainId][blockNumber] = blockHash_;
+        emit BlockHashPosted(chainId, blockNumber, blockHash_);
+    }
+
+    function blockHash(uint256 chainId, uint256 blockNumber) external view returns (bytes32) {
+        return blockHashes[chainId][blockNumber];
+    }
+}
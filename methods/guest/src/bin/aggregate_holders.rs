@@ -0,0 +1,103 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unused_doc_comments)]
+#![no_main]
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use alloy_primitives::{Address, B256, U256};
+use alloy_sol_types::SolValue;
+use guests::fail;
+use risc0_zkvm::{guest::env, sha::Digest};
+use zkpoap_types::{
+    AggregateData, BlockCommitment, GuestError, NullifierMode, PoapContract, ProofData,
+};
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // Rejected inputs are committed as a `GuestError` instead of panicking.
+    if let Err(err) = run() {
+        fail(err);
+    }
+}
+
+fn run() -> Result<(), GuestError> {
+    // Read the image ID of the `is-poap-owner` guest, the expected event, group, POAP contract
+    // and nullifier mode, and the journals of the aggregated receipts.
+    let (image_id, event_id, group_contract, chain_id, poap, nullifier_mode, journals): (
+        Digest,
+        U256,
+        Address,
+        u64,
+        PoapContract,
+        NullifierMode,
+        Vec<Vec<u8>>,
+    ) = env::read();
+
+    let mut nullifiers = BTreeSet::new();
+    let mut blocks = BTreeMap::new();
+    for journal in &journals {
+        // Each journal is only accepted if a receipt for it is added as an assumption, so the
+        // aggregate can only be verified if all individual proofs are valid.
+        env::verify(image_id, journal.as_slice()).map_err(|_| GuestError::InvalidAssumption)?;
+
+        let (proof_data, block, _) =
+            <(ProofData, BlockCommitment, BlockCommitment)>::abi_decode(journal, true)
+                .map_err(|_| GuestError::InvalidJournal)?;
+        if proof_data.eventId != event_id {
+            return Err(GuestError::WrongEvent(proof_data.eventId));
+        }
+        if proof_data.groupContract != group_contract {
+            return Err(GuestError::WrongGroup(proof_data.groupContract));
+        }
+        if proof_data.chainId != U256::from(chain_id) {
+            return Err(GuestError::WrongChain(proof_data.chainId));
+        }
+        // Any contract can answer `tokenDetailsOfOwnerByIndex`, so only proofs against the one
+        // POAP contract committed by the aggregate count.
+        if proof_data.poapContract != poap.address {
+            return Err(GuestError::WrongPoapContract(proof_data.poapContract));
+        }
+        if proof_data.poapChainId != U256::from(poap.chain_id) {
+            return Err(GuestError::WrongPoapChain(proof_data.poapChainId));
+        }
+        // A holder has one nullifier per mode, so mixing modes would count them twice.
+        if proof_data.nullifierMode != nullifier_mode as u8 {
+            return Err(GuestError::WrongNullifierMode(proof_data.nullifierMode));
+        }
+        // The nullifier identifies the holder, so every holder is counted at most once.
+        if !nullifiers.insert(proof_data.nullifier) {
+            return Err(GuestError::DuplicateNullifier(proof_data.nullifier));
+        }
+        // The proofs are only as sound as the blocks they were made at, so all of them are
+        // committed for the verifier to check.
+        blocks.insert((block.blockNumber, block.blockHash), block);
+    }
+
+    let aggregate = AggregateData {
+        imageId: B256::from_slice(image_id.as_bytes()),
+        eventId: event_id,
+        groupContract: group_contract,
+        chainId: U256::from(chain_id),
+        nullifierMode: nullifier_mode as u8,
+        poapContract: poap.address,
+        poapChainId: U256::from(poap.chain_id),
+        blocks: blocks.into_values().collect(),
+        count: U256::from(nullifiers.len()),
+    };
+    env::commit_slice(&aggregate.abi_encode());
+
+    Ok(())
+}
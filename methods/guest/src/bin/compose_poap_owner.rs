@@ -0,0 +1,100 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unused_doc_comments)]
+#![no_main]
+
+use alloy_primitives::B256;
+use alloy_sol_types::SolValue;
+use guests::fail;
+use risc0_zkvm::{guest::env, sha::Digest};
+use zkpoap_types::{
+    BlockCommitment, GroupBinding, GuestError, OwnershipData, ProofData, SemaphoreIdentity,
+    SignatureData,
+};
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // Rejected inputs are committed as a `GuestError` instead of panicking.
+    if let Err(err) = run() {
+        fail(err);
+    }
+}
+
+fn run() -> Result<(), GuestError> {
+    // Read the image IDs of the composed guests and their journals. The receipts proving the
+    // journals are added as assumptions by the host.
+    let (ownership_id, signature_id, ownership_journal, signature_journal): (
+        Digest,
+        Digest,
+        Vec<u8>,
+        Vec<u8>,
+    ) = env::read();
+
+    env::verify(ownership_id, ownership_journal.as_slice())
+        .map_err(|_| GuestError::InvalidAssumption)?;
+    env::verify(signature_id, signature_journal.as_slice())
+        .map_err(|_| GuestError::InvalidAssumption)?;
+
+    let (ownership, block_commitment) =
+        <(OwnershipData, BlockCommitment)>::abi_decode(&ownership_journal, true)
+            .map_err(|_| GuestError::InvalidJournal)?;
+    let signature = SignatureData::abi_decode(&signature_journal, true)
+        .map_err(|_| GuestError::InvalidJournal)?;
+
+    // The holder proven by the ownership guest must be the signer of the message.
+    if signature.owner != ownership.owner {
+        return Err(GuestError::WrongSigner(signature.owner));
+    }
+    if ownership.eventId != signature.eventId {
+        return Err(GuestError::WrongEvent(ownership.eventId));
+    }
+    // Typed data signatures expire; reject them if the deadline passed before the proven block.
+    if signature.deadline < block_commitment.blockTimestamp {
+        return Err(GuestError::SignatureExpired);
+    }
+
+    let binding = GroupBinding {
+        event_id: signature.eventId,
+        group_contract: signature.groupContract,
+        chain_id: signature.chainId.saturating_to(),
+        semaphore_identity: SemaphoreIdentity::Commitment(signature.semaphoreId),
+        nullifier_mode: signature.nullifierMode.try_into()?,
+    };
+
+    // Commit the same journal as `is_poap_owner`, without a holding age, followed by the image
+    // IDs of the composed guests so that verifiers can check which guests were composed.
+    env::commit_slice(
+        &ProofData {
+            eventId: binding.event_id,
+            semaphoreId: signature.semaphoreId,
+            nullifier: binding.nullifier(ownership.owner, ownership.tokenId),
+            chainId: signature.chainId,
+            groupContract: binding.group_contract,
+            nullifierMode: signature.nullifierMode,
+            poapContract: ownership.poapContract,
+            poapChainId: ownership.poapChainId,
+        }
+        .abi_encode(),
+    );
+    env::commit_slice(&block_commitment.abi_encode());
+    env::commit_slice(&block_commitment.abi_encode());
+    let image_ids = (
+        B256::from_slice(ownership_id.as_bytes()),
+        B256::from_slice(signature_id.as_bytes()),
+    );
+    env::commit_slice(&image_ids.abi_encode());
+
+    Ok(())
+}
@@ -16,89 +16,127 @@
 #![allow(unused_imports)]
 #![no_main]
 
-use std::char::from_digit;
-use alloy_primitives::{address, Address, Uint, U256};
-use alloy_sol_types::{sol, SolValue};
-use risc0_ethereum_view_call::{
-    config::GNOSIS_CHAIN_SPEC, ethereum::EthViewCallInput, BlockCommitment, ViewCall,
-};
+use alloy_primitives::{Sealable, U256};
+use alloy_sol_types::SolValue;
+use guests::fail;
+use risc0_ethereum_view_call::{ethereum::EthViewCallInput, EvmHeader, ViewCall};
 use risc0_zkvm::guest::env;
-risc0_zkvm::guest::entry!(main);
-use ark_bn254::Fr;
-use ark_ff::{BigInteger, PrimeField};
-use k256::{
-    ecdsa::{signature::Verifier, Signature, VerifyingKey},
-    EncodedPoint,
+use zkpoap_types::{
+    recover_owner, BlockCommitment, GroupBinding, GuestError, MessageFormat, PoapContract,
+    ProofData, POAP,
 };
-use light_poseidon::{parameters::bn254_x5, Poseidon, PoseidonHasher};
-
-const CONTRACT: Address = address!("22C1f6050E56d2876009903609a2cC3fEf83B415");
-const CALLER: Address = address!("6f22b9f222D9e9AF4481df55B863A567dfe1dd42");
+risc0_zkvm::guest::entry!(main);
 
-sol! {
-    interface POAP {
-        function tokenDetailsOfOwnerByIndex(address owner, uint256 index) external view returns (uint256, uint256);
+fn main() {
+    // Rejected inputs are committed as a `GuestError` instead of panicking, so that the host can
+    // tell them apart.
+    if let Err(err) = run() {
+        fail(err);
     }
 }
 
-sol!(
-    struct ProofData {
-        uint256 eventId;
-        uint256 semaphoreId;
-        bytes32 nullifier;
-    }
-);
-
-fn main() {
+fn run() -> Result<(), GuestError> {
     // Read the input from the guest environment.
     let call_input: EthViewCallInput = env::read();
-    let (encoded_verifying_key, message, signature, poap_index): (
-        EncodedPoint,
+    let (poap, binding, format, signature, poap_index): (
+        PoapContract,
+        GroupBinding,
+        MessageFormat,
         Vec<u8>,
-        Signature,
         U256,
     ) = env::read();
+    // Optionally, the input at a historical block and the index of the POAP at that block, to
+    // prove that the POAP has been held since then.
+    let held_since: Option<(EthViewCallInput, U256)> = env::read();
 
-    // Hash of signature.
-    let signature_hash = alloy_primitives::keccak256(signature.to_bytes());
-
-    // Get verifying key.
-    let verifying_key = VerifyingKey::from_encoded_point(&encoded_verifying_key).unwrap();
-
-    // Verify the signature.
-    verifying_key
-        .verify(&message, &signature)
-        .expect("Signature verification failed!");
+    // Recover the POAP owner from the 65-byte `r || s || v` signature over the expected message,
+    // so that a signature for any other group, event or commitment recovers a different address.
+    let owner = recover_owner(&signature, &binding.signing_hash(&format))?;
 
     // ViewCall to get event_id.
     let call: POAP::tokenDetailsOfOwnerByIndexCall = POAP::tokenDetailsOfOwnerByIndexCall {
-        owner: CALLER,
+        owner,
         index: poap_index,
     };
 
+    // The host includes the headers linking the historical block to the current block as
+    // ancestors, so the oldest ancestor is the historical block. `into_env` verifies the chain.
+    let oldest_ancestor = call_input.ancestors.last().map(|header| header.hash_slow());
+
     // Converts the input into a `ViewCallEnv` for execution.
-    let view_call_env = call_input.into_env().with_chain_spec(&GNOSIS_CHAIN_SPEC);
+    let view_call_env = call_input
+        .try_into_env()
+        .map_err(GuestError::InvalidState)?
+        .with_chain_spec(poap.chain_spec()?);
 
     // Commit the block hash and number used when deriving `view_call_env` to the journal.
-    let block_commitment = view_call_env.block_commitment();
+    let block_commitment =
+        BlockCommitment::new(view_call_env.block_commitment(), view_call_env.header());
+
+    // Typed data signatures expire; reject them if the deadline passed before the queried block.
+    format.check_deadline(view_call_env.header().timestamp())?;
 
     // Execute the view call.
-    let returns = ViewCall::new(call, CONTRACT)
-        .with_caller(CALLER)
-        .execute(view_call_env)
-        .clone();
+    let returns = ViewCall::new(call, poap.address)
+        .with_caller(owner)
+        .try_execute(view_call_env)
+        .map_err(GuestError::CallFailed)?;
     println!("View call result: token_id: {}, event_id: {}", returns._0, returns._1);
+    if returns._1 != binding.event_id {
+        return Err(GuestError::WrongEvent(returns._1));
+    }
 
-    // Create and commit proof data.
-    env::commit(
+    // The same POAP must have been owned at the historical block. Without a historical block, the
+    // POAP is only proven to be held since the current block.
+    let held_since_commitment = match held_since {
+        Some((historical_input, historical_index)) => {
+            let historical_env = historical_input
+                .try_into_env()
+                .map_err(GuestError::InvalidState)?
+                .with_chain_spec(poap.chain_spec()?);
+            let commitment =
+                BlockCommitment::new(historical_env.block_commitment(), historical_env.header());
+            if oldest_ancestor != Some(commitment.blockHash) {
+                return Err(GuestError::InvalidHistoricalBlock);
+            }
+
+            let call = POAP::tokenDetailsOfOwnerByIndexCall {
+                owner,
+                index: historical_index,
+            };
+            let historical_returns = ViewCall::new(call, poap.address)
+                .with_caller(owner)
+                .try_execute(historical_env)
+                .map_err(GuestError::CallFailed)?;
+            if historical_returns._0 != returns._0 {
+                return Err(GuestError::NotHeldSince);
+            }
+            commitment
+        }
+        None => block_commitment.clone(),
+    };
+
+    // Create and commit proof data. The journal is the bare ABI encoding, decoded as is by
+    // `POAPGroup`; `env::commit` would serialize it with the RISC Zero codec instead.
+    env::commit_slice(
         &ProofData {
-            eventId: returns._1,
-            semaphoreId: U256::from(0),
-            nullifier: signature_hash,
+            eventId: binding.event_id,
+            semaphoreId: binding.semaphore_identity.commitment(),
+            nullifier: binding.nullifier(owner, returns._0),
+            chainId: U256::from(binding.chain_id),
+            groupContract: binding.group_contract,
+            nullifierMode: binding.nullifier_mode as u8,
+            poapContract: poap.address,
+            poapChainId: U256::from(poap.chain_id),
         }
         .abi_encode(),
     );
 
     // Commit block commitment.
-    env::commit(&block_commitment.abi_encode());
+    env::commit_slice(&block_commitment.abi_encode());
+
+    // Commit the block since which the POAP has been held.
+    env::commit_slice(&held_since_commitment.abi_encode());
+
+    Ok(())
 }
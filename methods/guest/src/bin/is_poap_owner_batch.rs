@@ -0,0 +1,90 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unused_doc_comments)]
+#![allow(unused_imports)]
+#![no_main]
+
+use alloy_primitives::U256;
+use alloy_sol_types::SolValue;
+use guests::fail;
+use risc0_ethereum_view_call::{ethereum::EthViewCallInput, EvmHeader, ViewCall};
+use risc0_zkvm::guest::env;
+use zkpoap_types::{
+    recover_owner, BatchEntry, BlockCommitment, GuestError, PoapContract, ProofData, POAP,
+};
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // Rejected inputs are committed as a `GuestError` instead of panicking.
+    if let Err(err) = run() {
+        fail(err);
+    }
+}
+
+fn run() -> Result<(), GuestError> {
+    // Read the input from the guest environment.
+    let call_input: EthViewCallInput = env::read();
+    let (poap, entries): (PoapContract, Vec<BatchEntry>) = env::read();
+
+    // Converts the input into a `ViewCallEnv`, shared by the view calls of all entries.
+    let mut view_call_env = call_input
+        .try_into_env()
+        .map_err(GuestError::InvalidState)?
+        .with_chain_spec(poap.chain_spec()?);
+
+    // Commit the block hash and number used when deriving `view_call_env` to the journal.
+    let block_commitment =
+        BlockCommitment::new(view_call_env.block_commitment(), view_call_env.header());
+    let timestamp = view_call_env.header().timestamp();
+
+    let mut proof_data = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let binding = entry.binding;
+        let owner = recover_owner(&entry.signature, &binding.signing_hash(&entry.format))?;
+        if owner != entry.owner {
+            return Err(GuestError::WrongSigner(owner));
+        }
+        entry.format.check_deadline(timestamp)?;
+
+        let call = POAP::tokenDetailsOfOwnerByIndexCall {
+            owner,
+            index: entry.poap_index,
+        };
+        let returns = ViewCall::new(call, poap.address)
+            .with_caller(owner)
+            .try_call(&mut view_call_env)
+            .map_err(GuestError::CallFailed)?;
+        if returns._1 != binding.event_id {
+            return Err(GuestError::WrongEvent(returns._1));
+        }
+
+        proof_data.push(ProofData {
+            eventId: binding.event_id,
+            semaphoreId: binding.semaphore_identity.commitment(),
+            nullifier: binding.nullifier(owner, returns._0),
+            chainId: U256::from(binding.chain_id),
+            groupContract: binding.group_contract,
+            nullifierMode: binding.nullifier_mode as u8,
+            poapContract: poap.address,
+            poapChainId: U256::from(poap.chain_id),
+        });
+    }
+
+    // Commit the proof data of all entries and the block commitment, encoded as
+    // `abi.encode(proofData, blockCommitment)`.
+    env::commit_slice(&(proof_data, block_commitment).abi_encode_params());
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unused_doc_comments)]
+#![no_main]
+
+use std::collections::BTreeSet;
+
+use alloy_primitives::U256;
+use alloy_sol_types::SolValue;
+use guests::fail;
+use risc0_ethereum_view_call::{ethereum::EthViewCallInput, EvmHeader, ViewCall};
+use risc0_zkvm::guest::env;
+use zkpoap_types::{
+    recover_owner, BlockCommitment, GuestError, MessageFormat, PoapContract, SetBinding,
+    SetProofData, POAP,
+};
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // Rejected inputs are committed as a `GuestError` instead of panicking.
+    if let Err(err) = run() {
+        fail(err);
+    }
+}
+
+fn run() -> Result<(), GuestError> {
+    // Read the input from the guest environment.
+    let call_input: EthViewCallInput = env::read();
+    let (poap, binding, format, signature, poap_indices): (
+        PoapContract,
+        SetBinding,
+        MessageFormat,
+        Vec<u8>,
+        Vec<U256>,
+    ) = env::read();
+
+    binding.check_sorted()?;
+    let owner = recover_owner(&signature, &binding.signing_hash(&format))?;
+
+    // Converts the input into a `ViewCallEnv`, shared by all the view calls below.
+    let mut view_call_env = call_input
+        .try_into_env()
+        .map_err(GuestError::InvalidState)?
+        .with_chain_spec(poap.chain_spec()?);
+
+    // Commit the block hash and number used when deriving `view_call_env` to the journal.
+    let block_commitment =
+        BlockCommitment::new(view_call_env.block_commitment(), view_call_env.header());
+
+    format.check_deadline(view_call_env.header().timestamp())?;
+
+    // Count the distinct events of the set the owner holds a POAP from. POAPs from other events,
+    // or from an event that was already counted, do not contribute to the threshold.
+    let mut held_events = BTreeSet::new();
+    for index in poap_indices {
+        let call = POAP::tokenDetailsOfOwnerByIndexCall { owner, index };
+        let returns = ViewCall::new(call, poap.address)
+            .with_caller(owner)
+            .try_call(&mut view_call_env)
+            .map_err(GuestError::CallFailed)?;
+        if binding.event_ids.binary_search(&returns._1).is_ok() {
+            held_events.insert(returns._1);
+        }
+    }
+    let threshold_met = held_events.len() as u64 >= binding.threshold;
+
+    // Create and commit proof data. The event IDs themselves are never committed.
+    env::commit_slice(
+        &SetProofData {
+            eventSetCommitment: binding.event_set_commitment(),
+            threshold: U256::from(binding.threshold),
+            thresholdMet: threshold_met,
+            semaphoreId: binding.semaphore_identity.commitment(),
+            nullifier: binding.nullifier(owner),
+            chainId: U256::from(binding.chain_id),
+            groupContract: binding.group_contract,
+            poapContract: poap.address,
+            poapChainId: U256::from(poap.chain_id),
+        }
+        .abi_encode(),
+    );
+
+    // Commit block commitment.
+    env::commit_slice(&block_commitment.abi_encode());
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unused_doc_comments)]
+#![no_main]
+
+use alloy_primitives::U256;
+use alloy_sol_types::SolValue;
+use guests::fail;
+use risc0_zkvm::guest::env;
+use zkpoap_types::{recover_owner, GroupBinding, GuestError, MessageFormat, SignatureData};
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // Rejected inputs are committed as a `GuestError` instead of panicking.
+    if let Err(err) = run() {
+        fail(err);
+    }
+}
+
+fn run() -> Result<(), GuestError> {
+    // Read the input from the guest environment.
+    let (binding, format, signature): (GroupBinding, MessageFormat, Vec<u8>) = env::read();
+
+    // Recover the owner from the signature over the expected message, so that a signature for
+    // any other group, event or commitment recovers a different address.
+    let owner = recover_owner(&signature, &binding.signing_hash(&format))?;
+
+    // Commit the owner with the values the signature binds it to.
+    env::commit_slice(
+        &SignatureData {
+            owner,
+            eventId: binding.event_id,
+            semaphoreId: binding.semaphore_identity.commitment(),
+            chainId: U256::from(binding.chain_id),
+            groupContract: binding.group_contract,
+            nullifierMode: binding.nullifier_mode as u8,
+            deadline: format.deadline(),
+        }
+        .abi_encode(),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unused_doc_comments)]
+#![no_main]
+
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::SolValue;
+use guests::fail;
+use risc0_ethereum_view_call::{ethereum::EthViewCallInput, ViewCall};
+use risc0_zkvm::guest::env;
+use zkpoap_types::{BlockCommitment, GuestError, OwnershipData, PoapContract, POAP};
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    // Rejected inputs are committed as a `GuestError` instead of panicking.
+    if let Err(err) = run() {
+        fail(err);
+    }
+}
+
+fn run() -> Result<(), GuestError> {
+    // Read the input from the guest environment. Unlike `is_poap_owner`, the owner is given
+    // directly; that the caller controls it is proven by the `owner_signature` guest.
+    let call_input: EthViewCallInput = env::read();
+    let (poap, owner, event_id, poap_index): (PoapContract, Address, U256, U256) = env::read();
+
+    // Converts the input into a `ViewCallEnv` for execution.
+    let view_call_env = call_input
+        .try_into_env()
+        .map_err(GuestError::InvalidState)?
+        .with_chain_spec(poap.chain_spec()?);
+    let block_commitment =
+        BlockCommitment::new(view_call_env.block_commitment(), view_call_env.header());
+
+    let call = POAP::tokenDetailsOfOwnerByIndexCall {
+        owner,
+        index: poap_index,
+    };
+    let returns = ViewCall::new(call, poap.address)
+        .with_caller(owner)
+        .try_execute(view_call_env)
+        .map_err(GuestError::CallFailed)?;
+    if returns._1 != event_id {
+        return Err(GuestError::WrongEvent(returns._1));
+    }
+
+    // Commit the ownership and the block it was proven at.
+    env::commit_slice(
+        &OwnershipData {
+            owner,
+            eventId: event_id,
+            tokenId: returns._0,
+            poapContract: poap.address,
+            poapChainId: U256::from(poap.chain_id),
+        }
+        .abi_encode(),
+    );
+    env::commit_slice(&block_commitment.abi_encode());
+
+    Ok(())
+}
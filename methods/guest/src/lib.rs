@@ -0,0 +1,25 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by the zkPOAP guests. Their inputs and journals are defined in `zkpoap-types`,
+//! which the host uses as well.
+
+use risc0_zkvm::guest::env;
+use zkpoap_types::{GuestError, ERROR_EXIT_CODE};
+
+/// Commits the error to the journal and halts the guest with [ERROR_EXIT_CODE].
+pub fn fail(err: GuestError) -> ! {
+    env::commit(&err);
+    env::exit(ERROR_EXIT_CODE)
+}
@@ -15,115 +15,264 @@ include!(concat!(env!("OUT_DIR"), "/methods.rs"));
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[allow(unused_imports)]
-use alloy_primitives::{address, Address, U256};
-use alloy_sol_types::{sol, SolCall, SolValue};
-use anyhow::{Context, Result};
-use k256::{
-    ecdsa::{
-        signature::{Keypair, Signer},
-        Signature, SigningKey, VerifyingKey,
-    },
-    EncodedPoint,
-};
-use risc0_ethereum_view_call::{
-    ethereum::EthViewCallEnv, EvmHeader, ViewCall,
-};
-use rand_core::OsRng;
-use risc0_ethereum_view_call::config::GNOSIS_CHAIN_SPEC;
-use risc0_zkvm::{default_executor, ExecutorEnv, SessionInfo};
-use tracing_subscriber::EnvFilter;
-
-sol! {
-    interface POAP {
-        function tokenDetailsOfOwnerByIndex(address owner, uint256 index) external view returns (uint256, uint256);
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{address, eip191_hash_message, keccak256, Address, Signature, U256};
+    use alloy_sol_types::{SolCall, SolValue};
+    use anyhow::{bail, Context, Result};
+    use k256::ecdsa::SigningKey;
+    use risc0_ethereum_view_call::{
+        config::GNOSIS_CHAIN_SPEC,
+        ethereum::{EthViewCallEnv, EthViewCallInput},
+        EvmHeader, ViewCall,
+    };
+    use risc0_zkvm::{default_executor, ExecutorEnv, ExitCode, SessionInfo};
+    use tracing_subscriber::EnvFilter;
+    use zkpoap_types::{
+        GroupBinding, GuestError, MessageFormat, NullifierMode, PoapContract, ProofData,
+        SemaphoreIdentity, SetBinding, SetProofData, ERROR_EXIT_CODE, POAP,
+    };
+
+    /// Runs the guest and turns a rejected input into a [GuestError].
+    fn execute(env: ExecutorEnv, elf_binary: &[u8]) -> Result<SessionInfo> {
+        let session_info = default_executor()
+            .execute(env, elf_binary)
+            .context("failed to run executor")?;
+        match session_info.exit_code {
+            ExitCode::Halted(0) => Ok(session_info),
+            ExitCode::Halted(code) if code == u32::from(ERROR_EXIT_CODE) => {
+                Err(session_info.journal.decode::<GuestError>()?.into())
+            }
+            exit_code => bail!("guest exited with {:?}", exit_code),
+        }
     }
-}
 
-sol!(
-    struct ProofData {
-        uint256 eventId;
-        uint256 semaphoreId;
-        bytes32 nullifier;
+    /// Signs the message as `personal_sign` does and returns the 65-byte `r || s || v` signature.
+    fn sign_eip191(signing_key: &SigningKey, message: &[u8]) -> Result<Vec<u8>> {
+        let (signature, recovery_id) =
+            signing_key.sign_prehash_recoverable(eip191_hash_message(message).as_slice())?;
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        Ok(bytes)
     }
-);
-
-fn get_verification_inputs(
-    verifying_key: &VerifyingKey,
-    message: &[u8],
-    signature: &Signature,
-    poap_index: U256,
-    elf_binary: &[u8],
-) -> Result<SessionInfo> {
-
-    const CONTRACT: Address = address!("22C1f6050E56d2876009903609a2cC3fEf83B415");
-    const CALLER: Address = address!("6f22b9f222D9e9AF4481df55B863A567dfe1dd42");
-
-    let call: POAP::tokenDetailsOfOwnerByIndexCall = POAP::tokenDetailsOfOwnerByIndexCall {
-        owner: address!("6f22b9f222D9e9AF4481df55B863A567dfe1dd42"),
-        index: poap_index,
-    };
 
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-
-    let env = EthViewCallEnv::from_rpc(&std::env::var("RPC_URL").unwrap(), None)?
-        .with_chain_spec(&GNOSIS_CHAIN_SPEC);
-    let number = env.header().number();
-
-    let (input, returns) = ViewCall::new(call, CONTRACT)
-        .with_caller(CALLER)
-        .preflight(env)?;
-    println!(
-        "For block {} `{}` returns: token_id: {} - event_id: {}",
-        number,
-        POAP::tokenDetailsOfOwnerByIndexCall::SIGNATURE,
-        returns._0,
-        returns._1
-    );
-
-    let sig_data_inputs = (
-        verifying_key.to_encoded_point(true),
-        message,
-        signature,
-        poap_index,
-    );
-    
-    println!("Running the guest with the constructed input:");
-    let session_info = {
-        let env = ExecutorEnv::builder()
-            .write(&input)
-            .unwrap()
-            .write(&sig_data_inputs)
-            .unwrap()
-            .build()
-            .context("Failed to build exec env");
-        let exec = default_executor();
-        exec.execute(env?, elf_binary)
-            .context("failed to run executor")?
-    };
+    /// Returns the index of the first POAP of `owner` from the given event, searching at the given
+    /// block in an environment separate from the one preflighted for the guest.
+    fn find_poap_index(
+        rpc_url: &str,
+        block_number: u64,
+        poap: &PoapContract,
+        owner: Address,
+        event_id: U256,
+    ) -> Result<U256> {
+        let mut env = EthViewCallEnv::from_rpc(rpc_url, Some(block_number))?
+            .with_chain_spec(&GNOSIS_CHAIN_SPEC);
 
-    Ok(session_info)
-}
+        let balance = ViewCall::new(POAP::balanceOfCall { owner }, poap.address)
+            .preflight_call(&mut env)?
+            ._0;
+        for index in 0..balance.saturating_to::<u64>() {
+            let index = U256::from(index);
+            let call = POAP::tokenDetailsOfOwnerByIndexCall { owner, index };
+            let returns = ViewCall::new(call, poap.address).preflight_call(&mut env)?;
+            if returns._1 == event_id {
+                return Ok(index);
+            }
+        }
+        bail!("{} holds no POAP from event {}", owner, event_id)
+    }
 
+    fn get_verification_inputs(
+        binding: &GroupBinding,
+        signature: &[u8],
+        elf_binary: &[u8],
+    ) -> Result<SessionInfo> {
+        // The POAP contract on Gnosis Chain.
+        let poap = PoapContract {
+            address: address!("22C1f6050E56d2876009903609a2cC3fEf83B415"),
+            chain_id: 100,
+        };
 
-#[cfg(test)]
-mod tests {
+        // The guest recovers the POAP owner from the signature, so query the same address.
+        let owner =
+            Signature::try_from(signature)?.recover_address_from_msg(binding.eip191_message())?;
+
+        tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::from_default_env())
+            .init();
+
+        let rpc_url = std::env::var("RPC_URL").unwrap();
+        let env = EthViewCallEnv::from_rpc(&rpc_url, None)?.with_chain_spec(&GNOSIS_CHAIN_SPEC);
+        let number = env.header().number();
+
+        let poap_index = find_poap_index(&rpc_url, number, &poap, owner, binding.event_id)?;
+        let call: POAP::tokenDetailsOfOwnerByIndexCall = POAP::tokenDetailsOfOwnerByIndexCall {
+            owner,
+            index: poap_index,
+        };
+
+        let (input, returns) = ViewCall::new(call, poap.address)
+            .with_caller(owner)
+            .preflight(env)?;
+        println!(
+            "For block {} `{}` returns: token_id: {} - event_id: {}",
+            number,
+            POAP::tokenDetailsOfOwnerByIndexCall::SIGNATURE,
+            returns._0,
+            returns._1
+        );
+
+        let sig_data_inputs = (poap, binding, MessageFormat::Eip191, signature, poap_index);
+        // The POAP is not required to be held since a historical block.
+        let held_since: Option<(EthViewCallInput, U256)> = None;
+
+        println!("Running the guest with the constructed input:");
+        let session_info = {
+            let env = ExecutorEnv::builder()
+                .write(&input)
+                .unwrap()
+                .write(&sig_data_inputs)
+                .unwrap()
+                .write(&held_since)
+                .unwrap()
+                .build()
+                .context("Failed to build exec env");
+            execute(env?, elf_binary)?
+        };
 
-    use super::*;
+        Ok(session_info)
+    }
+
+    fn get_set_membership_inputs(
+        binding: &SetBinding,
+        signature: &[u8],
+        poap_indices: &[U256],
+        elf_binary: &[u8],
+    ) -> Result<SessionInfo> {
+        let poap = PoapContract {
+            address: address!("22C1f6050E56d2876009903609a2cC3fEf83B415"),
+            chain_id: 100,
+        };
+        let owner =
+            Signature::try_from(signature)?.recover_address_from_msg(binding.eip191_message())?;
+
+        // Preflight all the calls against the same environment, so that one input covers all of them.
+        let mut env = EthViewCallEnv::from_rpc(&std::env::var("RPC_URL").unwrap(), None)?
+            .with_chain_spec(&GNOSIS_CHAIN_SPEC);
+        for index in poap_indices {
+            let call = POAP::tokenDetailsOfOwnerByIndexCall {
+                owner,
+                index: *index,
+            };
+            let returns = ViewCall::new(call, poap.address)
+                .with_caller(owner)
+                .preflight_call(&mut env)?;
+            println!(
+                "Index {}: token_id: {} - event_id: {}",
+                index, returns._0, returns._1
+            );
+        }
+        let input = env.into_input()?;
+
+        let set_inputs = (
+            poap,
+            binding,
+            MessageFormat::Eip191,
+            signature,
+            poap_indices,
+        );
+        let env = ExecutorEnv::builder()
+            .write(&input)?
+            .write(&set_inputs)?
+            .build()
+            .context("Failed to build exec env")?;
+        execute(env, elf_binary)
+    }
+
+    #[test]
+    fn test_sign_eip191() {
+        // The well-known address of the private key `1`.
+        let signing_key = SigningKey::from_slice(&U256::from(1).to_be_bytes::<32>()).unwrap();
+        let message = b"zkPOAP";
+
+        let signature = sign_eip191(&signing_key, message).unwrap();
+        let signer = Signature::try_from(signature.as_slice())
+            .unwrap()
+            .recover_address_from_msg(message)
+            .unwrap();
+        assert_eq!(signer, address!("7E5F4552091A69125d5DfCb7b8C2659029395Bdf"));
+    }
 
     #[test]
     fn test_is_poap_owner() {
-        let signing_key = SigningKey::random(&mut OsRng); 
-        let message = b"This is a message that will be signed, and verified within the zkVM";
-        let signature: Signature = signing_key.sign(message);
-        let poap_index: U256 = U256::from(0);
+        let private_key = std::env::var("POAP_OWNER_PRIVATE_KEY").unwrap();
+        let signing_key =
+            SigningKey::from_slice(&hex::decode(private_key.trim_start_matches("0x")).unwrap())
+                .unwrap();
+        let binding = GroupBinding {
+            event_id: std::env::var("POAP_EVENT_ID").unwrap().parse().unwrap(),
+            group_contract: Address::ZERO,
+            chain_id: 100,
+            semaphore_identity: SemaphoreIdentity::Commitment(U256::from(1)),
+            nullifier_mode: NullifierMode::Address,
+        };
+        let signature = sign_eip191(&signing_key, binding.eip191_message().as_bytes()).unwrap();
 
         let session_info =
-        get_verification_inputs(signing_key.verifying_key(), message, &signature, poap_index, super::IS_POAP_OWNER_ELF)
-                .unwrap();
-        
+            get_verification_inputs(&binding, &signature, super::IS_POAP_OWNER_ELF).unwrap();
+
         let proof_data = ProofData::abi_decode(&session_info.journal.as_ref(), false).unwrap();
+        assert_eq!(proof_data.eventId, binding.event_id);
+        assert_eq!(proof_data.semaphoreId, U256::from(1));
+        assert_eq!(proof_data.chainId, U256::from(binding.chain_id));
+        assert_eq!(proof_data.groupContract, binding.group_contract);
+        assert_eq!(proof_data.nullifierMode, 0);
+        assert_eq!(
+            proof_data.poapContract,
+            address!("22C1f6050E56d2876009903609a2cC3fEf83B415")
+        );
+        assert_eq!(proof_data.poapChainId, U256::from(100));
+
+        // The nullifier only depends on the owner and the group.
+        let owner = Address::from_public_key(signing_key.verifying_key());
+        let group_id = keccak256((binding.group_contract, binding.event_id).abi_encode());
+        assert_eq!(
+            proof_data.nullifier,
+            keccak256((owner, U256::from_be_bytes(group_id.0)).abi_encode())
+        );
+    }
+
+    #[test]
+    fn test_is_poap_set_member() {
+        let private_key = std::env::var("POAP_OWNER_PRIVATE_KEY").unwrap();
+        let signing_key =
+            SigningKey::from_slice(&hex::decode(private_key.trim_start_matches("0x")).unwrap())
+                .unwrap();
+        let event_id: U256 = std::env::var("POAP_EVENT_ID").unwrap().parse().unwrap();
+        let binding = SetBinding {
+            event_ids: vec![event_id, event_id + U256::from(1)],
+            threshold: 1,
+            group_contract: Address::ZERO,
+            chain_id: 100,
+            semaphore_identity: SemaphoreIdentity::Commitment(U256::from(1)),
+        };
+        let signature = sign_eip191(&signing_key, binding.eip191_message().as_bytes()).unwrap();
+
+        let session_info = get_set_membership_inputs(
+            &binding,
+            &signature,
+            &[U256::from(0)],
+            super::IS_POAP_SET_MEMBER_ELF,
+        )
+        .unwrap();
+
+        let proof_data = SetProofData::abi_decode(&session_info.journal.as_ref(), false).unwrap();
+        assert_eq!(
+            proof_data.eventSetCommitment,
+            binding.event_set_commitment()
+        );
+        assert_eq!(proof_data.threshold, U256::from(1));
+        assert!(proof_data.thresholdMet);
+        assert_eq!(proof_data.semaphoreId, U256::from(1));
     }
 }
@@ -15,12 +15,21 @@
 use std::{io::Write, time::Duration};
 
 use alloy_primitives::FixedBytes;
-use anyhow::{Context, Result};
-use bonsai_sdk::alpha as bonsai_sdk;
+use anyhow::{bail, Context, Result};
+use bonsai_sdk::alpha_async as bonsai_sdk;
 use clap::Parser;
 use ethers::abi::Token;
 use risc0_ethereum_contracts::groth16::Seal;
-use risc0_zkvm::{compute_image_id, default_executor, is_dev_mode, ExecutorEnv, Receipt};
+use risc0_zkvm::{compute_image_id, default_executor, is_dev_mode, ExecutorEnv};
+use tokio::runtime::Runtime;
+
+/// Delay before the first status request after a Bonsai job was created, doubled after each
+/// request.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Maximum delay between status requests.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Deadline of a Bonsai proof request, including the snark.
+const PROOF_TIMEOUT: Duration = Duration::from_secs(4 * 3600);
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -105,72 +114,70 @@ impl DevModeProver {
 
 struct BonsaiProver {}
 impl BonsaiProver {
+    /// Proves on Bonsai, polling with backoff until [PROOF_TIMEOUT], or until Ctrl-C stops
+    /// polling. The Bonsai session itself keeps running in both cases.
     fn prove(elf: &[u8], input: &[u8]) -> Result<(Vec<u8>, FixedBytes<32>, Vec<u8>)> {
-        let client = bonsai_sdk::Client::from_env(risc0_zkvm::VERSION)?;
+        let runtime = Runtime::new().context("failed to start runtime")?;
+        runtime.block_on(async {
+            tokio::select! {
+                res = tokio::time::timeout(PROOF_TIMEOUT, Self::prove_async(elf, input)) => {
+                    res.with_context(|| format!("no proof within {:?}", PROOF_TIMEOUT))?
+                }
+                _ = tokio::signal::ctrl_c() => bail!("proof request was cancelled"),
+            }
+        })
+    }
+
+    async fn prove_async(elf: &[u8], input: &[u8]) -> Result<(Vec<u8>, FixedBytes<32>, Vec<u8>)> {
+        let client = bonsai_sdk::get_client_from_env(risc0_zkvm::VERSION).await?;
 
         // Compute the image_id, then upload the ELF with the image_id as its key.
         let image_id = compute_image_id(elf)?;
         let image_id_hex = image_id.to_string();
-        client.upload_img(&image_id_hex, elf.to_vec())?;
+        bonsai_sdk::upload_img(client.clone(), image_id_hex.clone(), elf.to_vec()).await?;
         log::info!("Image ID: 0x{}", image_id_hex);
 
         // Prepare input data and upload it.
-        let input_id = client.upload_input(input.to_vec())?;
+        let input_id = bonsai_sdk::upload_input(client.clone(), input.to_vec()).await?;
 
         // Start a session running the prover.
-        let session = client.create_session(image_id_hex, input_id, vec![])?;
+        let session =
+            bonsai_sdk::create_session(client.clone(), image_id_hex, input_id, vec![]).await?;
         log::info!("Created session: {}", session.uuid);
-        let _receipt = loop {
-            let res = session.status(&client)?;
-            if res.status == "RUNNING" {
-                log::info!(
+        let mut interval = POLL_INTERVAL;
+        loop {
+            interval = backoff(interval).await;
+            let res = bonsai_sdk::session_status(client.clone(), session.clone()).await?;
+            match res.status.as_str() {
+                "RUNNING" => log::info!(
                     "Current status: {} - state: {} - continue polling...",
                     res.status,
                     res.state.unwrap_or_default()
-                );
-                std::thread::sleep(Duration::from_secs(15));
-                continue;
-            }
-            if res.status == "SUCCEEDED" {
-                // Download the receipt, containing the output.
-                let receipt_url = res
-                    .receipt_url
-                    .context("API error, missing receipt on completed session")?;
-
-                let receipt_buf = client.download(&receipt_url)?;
-                let receipt: Receipt = bincode::deserialize(&receipt_buf)?;
-
-                break receipt;
-            } else {
-                panic!(
+                ),
+                "SUCCEEDED" => break,
+                _ => bail!(
                     "Workflow exited: {} - | err: {}",
                     res.status,
                     res.error_msg.unwrap_or_default()
-                );
+                ),
             }
-        };
+        }
 
         // Fetch the snark.
-        let snark_session = client.create_snark(session.uuid)?;
+        let snark_session = bonsai_sdk::create_snark(client.clone(), session.uuid).await?;
         log::info!("Created snark session: {}", snark_session.uuid);
+        let mut interval = POLL_INTERVAL;
         let snark_receipt = loop {
-            let res = snark_session.status(&client)?;
+            interval = backoff(interval).await;
+            let res = bonsai_sdk::snark_status(client.clone(), snark_session.clone()).await?;
             match res.status.as_str() {
-                "RUNNING" => {
-                    log::info!("Current status: {} - continue polling...", res.status,);
-                    std::thread::sleep(Duration::from_secs(15));
-                    continue;
-                }
-                "SUCCEEDED" => {
-                    break res.output.context("No snark generated :(")?;
-                }
-                _ => {
-                    panic!(
-                        "Workflow exited: {} err: {}",
-                        res.status,
-                        res.error_msg.unwrap_or_default()
-                    );
-                }
+                "RUNNING" => log::info!("Current status: {} - continue polling...", res.status),
+                "SUCCEEDED" => break res.output.context("No snark generated :(")?,
+                _ => bail!(
+                    "Workflow exited: {} err: {}",
+                    res.status,
+                    res.error_msg.unwrap_or_default()
+                ),
             }
         };
 
@@ -188,3 +195,9 @@ impl BonsaiProver {
         Ok((journal, post_state_digest, seal))
     }
 }
+
+/// Waits for the given delay before the next status request, and returns the doubled delay.
+async fn backoff(interval: Duration) -> Duration {
+    tokio::time::sleep(interval).await;
+    (interval * 2).min(MAX_POLL_INTERVAL)
+}
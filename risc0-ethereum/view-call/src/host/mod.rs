@@ -21,12 +21,12 @@ use self::{
 use crate::{
     ethereum::EthViewCallEnv, EvmHeader, MerkleTrie, ViewCall, ViewCallEnv, ViewCallInput,
 };
-use alloy_primitives::{Sealable, B256};
+use alloy_primitives::{Sealable, B256, U256};
 use alloy_sol_types::SolCall;
 use anyhow::{anyhow, ensure, Context};
 use ethers_providers::{Http, RetryClient};
 use log::{debug, info};
-use revm::primitives::HashMap;
+use revm::{primitives::HashMap, Database};
 
 pub mod db;
 pub mod provider;
@@ -62,28 +62,25 @@ impl<P: Provider> ViewCallEnv<ProofDb<P>, P::Header> {
 
         Ok(ViewCallEnv::new(db, header.seal_slow()))
     }
-}
 
-impl<C: SolCall> ViewCall<C> {
-    /// Executes the call to derive the corresponding [ViewCallInput].
+    /// Returns the hash of the ancestor block with the given number.
     ///
-    /// This method is used to preflight the call and get the required input for the guest.
-    pub fn preflight<P: Provider>(
-        self,
-        env: ViewCallEnv<ProofDb<P>, P::Header>,
-    ) -> anyhow::Result<(ViewCallInput<P::Header>, C::Return)> {
-        info!(
-            "Executing preflight for '{}' with caller {} on contract {}",
-            C::SIGNATURE,
-            self.caller,
-            self.contract
+    /// The headers linking the ancestor to the block of the environment are included in the
+    /// [ViewCallInput] as ancestors, so that the guest can verify the hash.
+    pub fn preflight_block_hash(&mut self, number: u64) -> anyhow::Result<B256> {
+        ensure!(
+            number < self.header.number(),
+            "block {number} is not an ancestor of block {}",
+            self.header.number()
         );
+        self.db
+            .block_hash(U256::from(number))
+            .map_err(|err| anyhow!("failed to get hash of block {number}: {err}"))
+    }
 
-        // initialize the database and execute the transaction
-        let mut db = env.db;
-        let transaction_result = self
-            .transact(&mut db, env.cfg_env, env.header.inner())
-            .map_err(|err| anyhow!(err))?;
+    /// Derives the [ViewCallInput] containing the state accessed by all preflighted calls.
+    pub fn into_input(self) -> anyhow::Result<ViewCallInput<P::Header>> {
+        let db = self.db;
 
         // use the same provider as the database
         let provider = db.provider();
@@ -104,7 +101,7 @@ impl<C: SolCall> ViewCall<C> {
         let state_trie =
             MerkleTrie::from_rlp_nodes(state_nodes).context("invalid account proof")?;
         ensure!(
-            env.header.state_root() == &state_trie.hash_slow(),
+            self.header.state_root() == &state_trie.hash_slow(),
             "root of the state trie does not match the header"
         );
 
@@ -147,15 +144,47 @@ impl<C: SolCall> ViewCall<C> {
         debug!("contracts: {}", contracts.len());
         debug!("blocks: {}", ancestors.len());
 
-        let header = env.header.into_inner();
-        let input = ViewCallInput {
+        let header = self.header.into_inner();
+        Ok(ViewCallInput {
             header,
             state_trie,
             storage_tries,
             contracts,
             ancestors,
-        };
+        })
+    }
+}
+
+impl<C: SolCall> ViewCall<C> {
+    /// Executes the call to derive the corresponding [ViewCallInput].
+    ///
+    /// This method is used to preflight the call and get the required input for the guest.
+    pub fn preflight<P: Provider>(
+        self,
+        mut env: ViewCallEnv<ProofDb<P>, P::Header>,
+    ) -> anyhow::Result<(ViewCallInput<P::Header>, C::Return)> {
+        let transaction_result = self.preflight_call(&mut env)?;
+
+        Ok((env.into_input()?, transaction_result))
+    }
+
+    /// Executes the call without consuming the environment, recording the accessed state.
+    ///
+    /// This method is used to preflight several calls against the same environment. The input
+    /// for the guest is then derived with [ViewCallEnv::into_input].
+    pub fn preflight_call<P: Provider>(
+        &self,
+        env: &mut ViewCallEnv<ProofDb<P>, P::Header>,
+    ) -> anyhow::Result<C::Return> {
+        info!(
+            "Executing preflight for '{}' with caller {} on contract {}",
+            C::SIGNATURE,
+            self.caller,
+            self.contract
+        );
 
-        Ok((input, transaction_result))
+        // execute the transaction on the database
+        self.transact(&mut env.db, env.cfg_env.clone(), env.header.inner())
+            .map_err(|err| anyhow!(err))
     }
 }
@@ -51,9 +51,19 @@ impl<H: EvmHeader> ViewCallInput<H> {
     ///
     /// This method verifies that the state matches the state root in the header and panics if not.
     pub fn into_env(self) -> ViewCallEnv<StateDB, H> {
+        self.try_into_env().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Converts the input into a [ViewCallEnv] for execution.
+    ///
+    /// This method verifies that the state matches the state root in the header and that the
+    /// ancestors form a valid chain, and returns an error if not.
+    pub fn try_into_env(self) -> Result<ViewCallEnv<StateDB, H>, String> {
         // verify that the state root matches the state trie
         let state_root = self.state_trie.hash_slow();
-        assert_eq!(self.header.state_root(), &state_root, "State root mismatch");
+        if self.header.state_root() != &state_root {
+            return Err("State root mismatch".to_string());
+        }
 
         // seal the header to compute its block hash
         let header = self.header.seal_slow();
@@ -65,13 +75,13 @@ impl<H: EvmHeader> ViewCallInput<H> {
         let mut previous_header = header.inner();
         for ancestor in &self.ancestors {
             let ancestor_hash = ancestor.hash_slow();
-            assert_eq!(
-                previous_header.parent_hash(),
-                &ancestor_hash,
-                "Invalid chain: block {} is not the parent of block {}",
-                ancestor.number(),
-                previous_header.number()
-            );
+            if previous_header.parent_hash() != &ancestor_hash {
+                return Err(format!(
+                    "Invalid chain: block {} is not the parent of block {}",
+                    ancestor.number(),
+                    previous_header.number()
+                ));
+            }
             block_hashes.insert(ancestor.number(), ancestor_hash);
             previous_header = ancestor;
         }
@@ -83,7 +93,7 @@ impl<H: EvmHeader> ViewCallInput<H> {
             block_hashes,
         );
 
-        ViewCallEnv::new(db, header)
+        Ok(ViewCallEnv::new(db, header))
     }
 }
 
@@ -167,11 +177,46 @@ impl<C: SolCall> ViewCall<C> {
     /// Executes the view call using the given environment.
     #[inline]
     pub fn execute<D: Database, H: EvmHeader>(self, env: ViewCallEnv<D, H>) -> C::Return
+    where
+        <D as Database>::Error: Debug,
+    {
+        self.try_execute(env).unwrap()
+    }
+
+    /// Executes the view call using the given environment, returning an error if the call fails.
+    #[inline]
+    pub fn try_execute<D: Database, H: EvmHeader>(
+        self,
+        env: ViewCallEnv<D, H>,
+    ) -> Result<C::Return, String>
     where
         <D as Database>::Error: Debug,
     {
         self.transact(env.db, env.cfg_env, env.header.inner())
-            .unwrap()
+    }
+
+    /// Executes the view call using the given environment without consuming it.
+    ///
+    /// This allows executing several view calls against the same state.
+    #[inline]
+    pub fn call<D: Database, H: EvmHeader>(&self, env: &mut ViewCallEnv<D, H>) -> C::Return
+    where
+        <D as Database>::Error: Debug,
+    {
+        self.try_call(env).unwrap()
+    }
+
+    /// Executes the view call using the given environment without consuming it, returning an
+    /// error if the call fails.
+    #[inline]
+    pub fn try_call<D: Database, H: EvmHeader>(
+        &self,
+        env: &mut ViewCallEnv<D, H>,
+    ) -> Result<C::Return, String>
+    where
+        <D as Database>::Error: Debug,
+    {
+        self.transact(&mut env.db, env.cfg_env.clone(), env.header.inner())
     }
 
     /// Transacts a transaction corresponding to the call data.
@@ -52,6 +52,32 @@ fn erc20_balance_of() {
     assert_eq!(result._0, uint!(3000000000000000_U256));
 }
 
+#[test]
+fn erc20_multiple_calls() {
+    let contract = address!("dAC17F958D2ee523a2206206994597C13D831ec7"); // USDT
+    sol! {
+        function balanceOf(address account) external view returns (uint);
+    }
+    let call = balanceOfCall {
+        account: address!("F977814e90dA44bFA03b6295A0616a897441aceC"), // Binance 8
+    };
+
+    // run the preflight for two calls against the same environment
+    let provider = EthFileProvider::from_file(&RPC_CACHE_FILE.into()).unwrap();
+    let mut env = EthViewCallEnv::from_provider(provider, BLOCK).unwrap();
+    let view_call = ViewCall::new(call, contract);
+    view_call.preflight_call(&mut env).unwrap();
+    view_call.preflight_call(&mut env).unwrap();
+    let input = env.into_input().unwrap();
+
+    // execute both calls
+    let mut env = input.into_env();
+    for _ in 0..2 {
+        let result = view_call.call(&mut env);
+        assert_eq!(result._0, uint!(3000000000000000_U256));
+    }
+}
+
 #[test]
 fn uniswap_exact_output_single() {
     // mimic tx 0x241c81c3aa4c68cd07ae03a756050fc47fd91918a710250453d34c6db9d11997
@@ -21,19 +21,46 @@ import {console2} from "forge-std/console2.sol";
 import {IRiscZeroVerifier} from "risc0/IRiscZeroVerifier.sol";
 import {ControlID, RiscZeroGroth16Verifier} from "risc0/groth16/RiscZeroGroth16Verifier.sol";
 
+import {IBlockHashOracle} from "../contracts/IBlockHashOracle.sol";
 import {POAPGroup} from "../contracts/POAPGroup.sol";
+import {TrustedBlockHashOracle} from "../contracts/TrustedBlockHashOracle.sol";
 
 /// @notice Deployment script for the RISC Zero starter project.
 /// @dev Use the following environment variable to control the deployment:
-///     * ETH_WALLET_PRIVATE_KEY private key of the wallet to be used for deployment.
+///     * SEMAPHORE_ADDRESS address of the Semaphore contract, by default the one of Sepolia.
+///     * BLOCK_HASH_ORACLE address of the oracle providing the block hashes of Gnosis Chain, required
+///       unless deploying on Gnosis Chain or with DEPLOY_BLOCK_HASH_ORACLE.
+///     * DEPLOY_BLOCK_HASH_ORACLE set to true to deploy a `TrustedBlockHashOracle` posted to by the
+///       deployer instead, for testnets and demos.
+///
+/// The deployer is the wallet selected on the command line of `forge script`, e.g. with
+/// `--keystore`, `--aws` or, on anvil, `--unlocked --sender`.
 ///
 /// See the Foundry documentation for more information about Solidity scripts.
 /// https://book.getfoundry.sh/tutorials/solidity-scripting
 contract POAPGroupDeploy is Script {
+    /// Chain ID of Gnosis Chain, where the POAP contract is.
+    uint256 constant POAP_CHAIN_ID = 100;
+
     function run() external {
-        uint256 deployerKey = uint256(vm.envBytes32("ETH_WALLET_PRIVATE_KEY"));
+        // The POAP contract is on Gnosis Chain, so the group needs the block hashes of Gnosis
+        // Chain on any other chain; check this before deploying anything.
+        IBlockHashOracle blockHashOracle =
+            IBlockHashOracle(vm.envOr("BLOCK_HASH_ORACLE", address(0)));
+        bool deployBlockHashOracle = vm.envOr("DEPLOY_BLOCK_HASH_ORACLE", false);
+        require(
+            block.chainid == POAP_CHAIN_ID
+                || address(blockHashOracle) != address(0)
+                || deployBlockHashOracle,
+            "Deploying outside of Gnosis Chain requires BLOCK_HASH_ORACLE, the address of an IBlockHashOracle of Gnosis Chain, or DEPLOY_BLOCK_HASH_ORACLE=true to deploy a TrustedBlockHashOracle"
+        );
+
+        vm.startBroadcast();
 
-        vm.startBroadcast(deployerKey);
+        if (block.chainid != POAP_CHAIN_ID && address(blockHashOracle) == address(0)) {
+            blockHashOracle = new TrustedBlockHashOracle();
+            console2.log("Deployed TrustedBlockHashOracle to", address(blockHashOracle));
+        }
 
         IRiscZeroVerifier verifier = new RiscZeroGroth16Verifier(
             ControlID.CONTROL_ID_0,
@@ -43,8 +70,13 @@ contract POAPGroupDeploy is Script {
 
         POAPGroup poapGroup = new POAPGroup(
             verifier,
-            0x3889927F0B5Eb1a02C6E2C20b39a1Bd4EAd76131,
-            10
+            vm.envOr("SEMAPHORE_ADDRESS", address(0x3889927F0B5Eb1a02C6E2C20b39a1Bd4EAd76131)),
+            10,
+            POAPGroup.NullifierMode.Address,
+            0x22C1f6050E56d2876009903609a2cC3fEf83B415,
+            POAP_CHAIN_ID,
+            type(uint256).max,
+            blockHashOracle
         );
         console2.log("Deployed POAPGroup to", address(poapGroup));
 
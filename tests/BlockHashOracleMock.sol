@@ -0,0 +1,32 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+pragma solidity ^0.8.20;
+
+import {IBlockHashOracle} from "../contracts/IBlockHashOracle.sol";
+
+/// Returns the block hashes set by the tests, as a bridge relaying them would.
+contract BlockHashOracleMock is IBlockHashOracle {
+    mapping(uint256 => mapping(uint256 => bytes32)) internal blockHashes;
+
+    function setBlockHash(uint256 chainId, uint256 blockNumber, bytes32 hash) external {
+        blockHashes[chainId][blockNumber] = hash;
+    }
+
+    function blockHash(uint256 chainId, uint256 blockNumber) external view returns (bytes32) {
+        return blockHashes[chainId][blockNumber];
+    }
+}
@@ -20,7 +20,235 @@ import {RiscZeroCheats} from "risc0/RiscZeroCheats.sol";
 import {console2} from "forge-std/console2.sol";
 import {Test} from "forge-std/Test.sol";
 import {IRiscZeroVerifier} from "risc0/IRiscZeroVerifier.sol";
+import {MockRiscZeroVerifier} from "risc0/MockRiscZeroVerifier.sol";
+import {IBlockHashOracle} from "../contracts/IBlockHashOracle.sol";
 import {POAPGroup} from "../contracts/POAPGroup.sol";
+import {BlockHashOracleMock} from "./BlockHashOracleMock.sol";
 import {Elf} from "./Elf.sol"; 
+import {SemaphoreMock} from "./SemaphoreMock.sol";
 
-contract POAPGroupTest is RiscZeroCheats, Test {}
+contract POAPGroupTest is RiscZeroCheats, Test {
+    address constant POAP_CONTRACT = 0x22C1f6050E56d2876009903609a2cC3fEf83B415;
+    uint256 constant POAP_CHAIN_ID = 100;
+    uint256 constant EVENT_ID = 1;
+    uint256 constant HELD_SINCE_BLOCK = 1000;
+    uint256 constant SEMAPHORE_ID = 42;
+    bytes32 constant BLOCK_HASH = bytes32(uint256(1));
+
+    IRiscZeroVerifier verifier;
+    SemaphoreMock semaphore;
+    BlockHashOracleMock oracle;
+    POAPGroup group;
+
+    function setUp() public {
+        verifier = new MockRiscZeroVerifier();
+        semaphore = new SemaphoreMock();
+        oracle = new BlockHashOracleMock();
+        oracle.setBlockHash(POAP_CHAIN_ID, HELD_SINCE_BLOCK, BLOCK_HASH);
+        group = newGroup(POAP_CHAIN_ID, oracle);
+    }
+
+    function newGroup(uint256 poapChainId, IBlockHashOracle blockHashOracle)
+        internal
+        returns (POAPGroup)
+    {
+        return new POAPGroup(
+            verifier,
+            address(semaphore),
+            EVENT_ID,
+            POAPGroup.NullifierMode.Address,
+            POAP_CONTRACT,
+            poapChainId,
+            HELD_SINCE_BLOCK,
+            blockHashOracle
+        );
+    }
+
+    /// Returns the address-mode nullifier of `owner` computed by the guests, i.e.
+    /// `keccak256(abi.encode(owner, groupId))` with `groupId = keccak256(abi.encode(group, eventId))`.
+    function nullifier(address owner) internal view returns (bytes32) {
+        uint256 groupId = uint256(keccak256(abi.encode(address(group), EVENT_ID)));
+        return keccak256(abi.encode(owner, groupId));
+    }
+
+    /// Returns the proof data of a valid proof for `group`.
+    function proofData(address owner) internal view returns (POAPGroup.ProofData memory) {
+        return POAPGroup.ProofData({
+            eventId: EVENT_ID,
+            semaphoreId: SEMAPHORE_ID,
+            nullifier: nullifier(owner),
+            chainId: block.chainid,
+            groupContract: address(group),
+            nullifierMode: uint8(POAPGroup.NullifierMode.Address),
+            poapContract: POAP_CONTRACT,
+            poapChainId: group.poapChainId()
+        });
+    }
+
+    /// Returns the commitment to a block at the current time.
+    function blockCommitment(uint256 blockNumber, bytes32 blockHash)
+        internal
+        view
+        returns (POAPGroup.BlockCommitment memory)
+    {
+        return POAPGroup.BlockCommitment({
+            blockHash: blockHash,
+            blockNumber: blockNumber,
+            blockTimestamp: block.timestamp
+        });
+    }
+
+    /// Encodes the journal of `is_poap_owner` for the POAP held since the given block.
+    function ownerJournal(
+        POAPGroup.BlockCommitment memory commitment,
+        POAPGroup.BlockCommitment memory heldSince
+    ) internal view returns (bytes memory) {
+        return abi.encode(proofData(address(1)), commitment, heldSince);
+    }
+
+    /// Encodes the journal of `compose_poap_owner` with a block of the POAP chain known to the
+    /// oracle, and the given image IDs of the composed guests.
+    function composedJournal(bytes32 ownershipId, bytes32 signatureId)
+        internal
+        view
+        returns (bytes memory)
+    {
+        POAPGroup.BlockCommitment memory commitment = blockCommitment(HELD_SINCE_BLOCK, BLOCK_HASH);
+        return abi.encode(proofData(address(1)), commitment, commitment, ownershipId, signatureId);
+    }
+
+    /// Encodes the journal of the batch guest for the given owners.
+    function batchJournal(address[] memory owners) internal view returns (bytes memory) {
+        POAPGroup.ProofData[] memory entries = new POAPGroup.ProofData[](owners.length);
+        for (uint256 i = 0; i < owners.length; i++) {
+            entries[i] = proofData(owners[i]);
+        }
+        return abi.encode(entries, blockCommitment(HELD_SINCE_BLOCK, BLOCK_HASH));
+    }
+
+    function test_JoinGroup() public {
+        POAPGroup.BlockCommitment memory commitment = blockCommitment(HELD_SINCE_BLOCK, BLOCK_HASH);
+        group.joinGroup(ownerJournal(commitment, commitment), bytes32(0), new bytes(0));
+
+        assertTrue(group.nullifires(proofData(address(1)).nullifier));
+        uint256[] memory members = semaphore.getMembers(group.groupId());
+        assertEq(members.length, 1);
+        assertEq(members[0], SEMAPHORE_ID);
+    }
+
+    function test_RevertWhen_BlockIsTooOld() public {
+        POAPGroup.BlockCommitment memory commitment = blockCommitment(HELD_SINCE_BLOCK, BLOCK_HASH);
+        bytes memory stale = ownerJournal(commitment, commitment);
+        vm.warp(block.timestamp + group.MAX_BLOCK_AGE() + 1);
+
+        vm.expectRevert("BLOCK_TOO_OLD");
+        group.joinGroup(stale, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_BlockHashDiffers() public {
+        // E.g. the header of a fabricated chain with a recent timestamp.
+        POAPGroup.BlockCommitment memory commitment =
+            blockCommitment(HELD_SINCE_BLOCK, bytes32(uint256(2)));
+        bytes memory data = ownerJournal(commitment, commitment);
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_BlockIsUnknown() public {
+        POAPGroup.BlockCommitment memory commitment =
+            blockCommitment(HELD_SINCE_BLOCK - 1, BLOCK_HASH);
+        bytes memory data = ownerJournal(commitment, commitment);
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_HeldTooRecently() public {
+        POAPGroup.BlockCommitment memory commitment = blockCommitment(HELD_SINCE_BLOCK, BLOCK_HASH);
+        POAPGroup.BlockCommitment memory heldSince =
+            blockCommitment(HELD_SINCE_BLOCK + 1, BLOCK_HASH);
+        bytes memory data = ownerJournal(commitment, heldSince);
+
+        vm.expectRevert("HELD_TOO_RECENTLY");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_SameChainBlockIsStale() public {
+        group = newGroup(block.chainid, IBlockHashOracle(address(0)));
+        // `blockhash` is zero for blocks older than the last 256 blocks.
+        vm.roll(HELD_SINCE_BLOCK + 257);
+        POAPGroup.BlockCommitment memory commitment = blockCommitment(HELD_SINCE_BLOCK, BLOCK_HASH);
+        bytes memory data = ownerJournal(commitment, commitment);
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_SameChainBlockHashDiffers() public {
+        group = newGroup(block.chainid, IBlockHashOracle(address(0)));
+        vm.roll(HELD_SINCE_BLOCK + 1);
+        POAPGroup.BlockCommitment memory commitment =
+            blockCommitment(HELD_SINCE_BLOCK, bytes32(uint256(2)));
+        bytes memory data = ownerJournal(commitment, commitment);
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_NoBlockHashOracle() public {
+        vm.expectRevert("NO_BLOCK_HASH_ORACLE");
+        newGroup(POAP_CHAIN_ID, IBlockHashOracle(address(0)));
+    }
+
+    function test_JoinGroupBatch_SkipsUsedNullifiers() public {
+        bytes memory journal =
+            composedJournal(group.ownershipImageId(), group.signatureImageId());
+        group.joinGroupComposed(journal, bytes32(0), new bytes(0));
+
+        // The first owner already joined, and the second one is in the batch twice.
+        address[] memory owners = new address[](3);
+        owners[0] = address(1);
+        owners[1] = address(2);
+        owners[2] = address(2);
+        group.joinGroupBatch(batchJournal(owners), bytes32(0), new bytes(0));
+
+        assertTrue(group.nullifires(proofData(address(2)).nullifier));
+        assertEq(semaphore.getMembers(group.groupId()).length, 2);
+    }
+
+    function test_JoinGroupComposed() public {
+        bytes memory journal =
+            composedJournal(group.ownershipImageId(), group.signatureImageId());
+        group.joinGroupComposed(journal, bytes32(0), new bytes(0));
+
+        assertTrue(group.nullifires(proofData(address(1)).nullifier));
+        uint256[] memory members = semaphore.getMembers(group.groupId());
+        assertEq(members.length, 1);
+        assertEq(members[0], SEMAPHORE_ID);
+    }
+
+    function test_RevertWhen_OwnershipImageIdDiffers() public {
+        // E.g. the receipt of a guest that commits any owner without querying the POAP contract.
+        bytes memory journal = composedJournal(group.imageId(), group.signatureImageId());
+
+        vm.expectRevert("INVALID_OWNERSHIP_IMAGE_ID");
+        group.joinGroupComposed(journal, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_SignatureImageIdDiffers() public {
+        // E.g. the receipt of a guest that commits any owner without checking a signature.
+        bytes memory journal = composedJournal(group.ownershipImageId(), group.imageId());
+
+        vm.expectRevert("INVALID_SIGNATURE_IMAGE_ID");
+        group.joinGroupComposed(journal, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_ImageIdsAreSwapped() public {
+        bytes memory journal =
+            composedJournal(group.signatureImageId(), group.ownershipImageId());
+
+        vm.expectRevert("INVALID_OWNERSHIP_IMAGE_ID");
+        group.joinGroupComposed(journal, bytes32(0), new bytes(0));
+    }
+}
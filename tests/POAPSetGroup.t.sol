@@ -0,0 +1,210 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+pragma solidity ^0.8.20;
+
+import {Test} from "forge-std/Test.sol";
+import {IRiscZeroVerifier} from "risc0/IRiscZeroVerifier.sol";
+import {MockRiscZeroVerifier} from "risc0/MockRiscZeroVerifier.sol";
+import {IBlockHashOracle} from "../contracts/IBlockHashOracle.sol";
+import {POAPSetGroup} from "../contracts/POAPSetGroup.sol";
+import {BlockHashOracleMock} from "./BlockHashOracleMock.sol";
+import {SemaphoreMock} from "./SemaphoreMock.sol";
+
+contract POAPSetGroupTest is Test {
+    address constant POAP_CONTRACT = 0x22C1f6050E56d2876009903609a2cC3fEf83B415;
+    uint256 constant POAP_CHAIN_ID = 100;
+    uint256 constant THRESHOLD = 2;
+    uint256 constant SEMAPHORE_ID = 42;
+    uint256 constant BLOCK_NUMBER = 1000;
+    bytes32 constant BLOCK_HASH = bytes32(uint256(1));
+
+    IRiscZeroVerifier verifier;
+    SemaphoreMock semaphore;
+    BlockHashOracleMock oracle;
+    POAPSetGroup group;
+    bytes32 eventSetCommitment;
+
+    function setUp() public {
+        uint256[] memory eventIds = new uint256[](3);
+        eventIds[0] = 1;
+        eventIds[1] = 2;
+        eventIds[2] = 3;
+        eventSetCommitment = keccak256(abi.encode(eventIds));
+
+        verifier = new MockRiscZeroVerifier();
+        semaphore = new SemaphoreMock();
+        oracle = new BlockHashOracleMock();
+        oracle.setBlockHash(POAP_CHAIN_ID, BLOCK_NUMBER, BLOCK_HASH);
+        group = newGroup(POAP_CHAIN_ID, oracle);
+    }
+
+    function newGroup(uint256 poapChainId, IBlockHashOracle blockHashOracle)
+        internal
+        returns (POAPSetGroup)
+    {
+        return new POAPSetGroup(
+            verifier,
+            address(semaphore),
+            eventSetCommitment,
+            THRESHOLD,
+            POAP_CONTRACT,
+            poapChainId,
+            blockHashOracle
+        );
+    }
+
+    /// Returns the nullifier of `owner` computed by `is_poap_set_member`, i.e.
+    /// `keccak256(abi.encode(owner, groupId))` with
+    /// `groupId = keccak256(abi.encode(group, eventSetCommitment))`.
+    function nullifier(address owner) internal view returns (bytes32) {
+        uint256 groupId = uint256(keccak256(abi.encode(address(group), eventSetCommitment)));
+        return keccak256(abi.encode(owner, groupId));
+    }
+
+    /// Returns the proof data of a valid proof for `group`, committed by `is_poap_set_member`.
+    function proofData(address owner) internal view returns (POAPSetGroup.SetProofData memory) {
+        return POAPSetGroup.SetProofData({
+            eventSetCommitment: eventSetCommitment,
+            threshold: THRESHOLD,
+            thresholdMet: true,
+            semaphoreId: SEMAPHORE_ID,
+            nullifier: nullifier(owner),
+            chainId: block.chainid,
+            groupContract: address(group),
+            poapContract: POAP_CONTRACT,
+            poapChainId: group.poapChainId()
+        });
+    }
+
+    /// Encodes the journal with the given block of the POAP chain at the current time.
+    function journal(POAPSetGroup.SetProofData memory data, uint256 blockNumber, bytes32 blockHash)
+        internal
+        view
+        returns (bytes memory)
+    {
+        POAPSetGroup.BlockCommitment memory commitment = POAPSetGroup.BlockCommitment({
+            blockHash: blockHash,
+            blockNumber: blockNumber,
+            blockTimestamp: block.timestamp
+        });
+        return abi.encode(data, commitment);
+    }
+
+    /// Encodes the journal with a block of the POAP chain known to the oracle.
+    function journal(POAPSetGroup.SetProofData memory data) internal view returns (bytes memory) {
+        return journal(data, BLOCK_NUMBER, BLOCK_HASH);
+    }
+
+    function test_JoinGroup() public {
+        POAPSetGroup.SetProofData memory data = proofData(address(1));
+        group.joinGroup(journal(data), bytes32(0), new bytes(0));
+
+        assertTrue(group.nullifiers(data.nullifier));
+        uint256[] memory members = semaphore.getMembers(group.groupId());
+        assertEq(members.length, 1);
+        assertEq(members[0], SEMAPHORE_ID);
+    }
+
+    function test_RevertWhen_NullifierIsUsed() public {
+        bytes memory data = journal(proofData(address(1)));
+        group.joinGroup(data, bytes32(0), new bytes(0));
+
+        vm.expectRevert("DUPLICATE_PROOF");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_EventSetDiffers() public {
+        POAPSetGroup.SetProofData memory data = proofData(address(1));
+        uint256[] memory eventIds = new uint256[](1);
+        eventIds[0] = 1;
+        data.eventSetCommitment = keccak256(abi.encode(eventIds));
+
+        vm.expectRevert("INVALID_EVENT_SET");
+        group.joinGroup(journal(data), bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_ThresholdDiffers() public {
+        POAPSetGroup.SetProofData memory data = proofData(address(1));
+        data.threshold = 1;
+
+        vm.expectRevert("INVALID_THRESHOLD");
+        group.joinGroup(journal(data), bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_ThresholdIsNotMet() public {
+        POAPSetGroup.SetProofData memory data = proofData(address(1));
+        data.thresholdMet = false;
+
+        vm.expectRevert("THRESHOLD_NOT_MET");
+        group.joinGroup(journal(data), bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_BlockIsTooOld() public {
+        POAPSetGroup.SetProofData memory data = proofData(address(1));
+        bytes memory stale = journal(data);
+        vm.warp(block.timestamp + group.MAX_BLOCK_AGE() + 1);
+
+        vm.expectRevert("BLOCK_TOO_OLD");
+        group.joinGroup(stale, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_BlockHashDiffers() public {
+        // E.g. the header of a fabricated chain with a recent timestamp.
+        bytes memory data = journal(proofData(address(1)), BLOCK_NUMBER, bytes32(uint256(2)));
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_BlockIsUnknown() public {
+        bytes memory data = journal(proofData(address(1)), BLOCK_NUMBER - 1, BLOCK_HASH);
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_SameChainBlockIsStale() public {
+        group = newGroup(block.chainid, IBlockHashOracle(address(0)));
+        // `blockhash` is zero for blocks older than the last 256 blocks.
+        vm.roll(BLOCK_NUMBER + 257);
+        bytes memory data = journal(proofData(address(1)), BLOCK_NUMBER, BLOCK_HASH);
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_SameChainBlockHashDiffers() public {
+        group = newGroup(block.chainid, IBlockHashOracle(address(0)));
+        vm.roll(BLOCK_NUMBER + 1);
+        bytes memory data = journal(proofData(address(1)), BLOCK_NUMBER, bytes32(uint256(2)));
+
+        vm.expectRevert("INVALID_BLOCK");
+        group.joinGroup(data, bytes32(0), new bytes(0));
+    }
+
+    function test_RevertWhen_NoBlockHashOracle() public {
+        vm.expectRevert("NO_BLOCK_HASH_ORACLE");
+        newGroup(POAP_CHAIN_ID, IBlockHashOracle(address(0)));
+    }
+
+    function test_RevertWhen_SealIsInvalid() public {
+        bytes memory data = journal(proofData(address(1)));
+
+        vm.expectRevert();
+        group.joinGroup(data, bytes32(0), hex"01");
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+pragma solidity ^0.8.20;
+
+/// Records the calls of the group contracts to Semaphore, without any Merkle tree.
+contract SemaphoreMock {
+    mapping(uint256 => address) public admins;
+    mapping(uint256 => uint256[]) internal members;
+
+    function createGroup(uint256 groupId, uint256, address admin) external {
+        require(admins[groupId] == address(0), "GROUP_EXISTS");
+        admins[groupId] = admin;
+    }
+
+    function addMember(uint256 groupId, uint256 identityCommitment) external {
+        require(admins[groupId] == msg.sender, "NOT_ADMIN");
+        members[groupId].push(identityCommitment);
+    }
+
+    function verifyProof(uint256, uint256, uint256, uint256, uint256, uint256[8] calldata) external pure {}
+
+    function getMembers(uint256 groupId) external view returns (uint256[] memory) {
+        return members[groupId];
+    }
+}
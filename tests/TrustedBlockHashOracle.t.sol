@@ -0,0 +1,56 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+pragma solidity ^0.8.20;
+
+import {Test} from "forge-std/Test.sol";
+import {TrustedBlockHashOracle} from "../contracts/TrustedBlockHashOracle.sol";
+
+contract TrustedBlockHashOracleTest is Test {
+    uint256 constant CHAIN_ID = 100;
+    uint256 constant BLOCK_NUMBER = 1000;
+    bytes32 constant BLOCK_HASH = bytes32(uint256(1));
+
+    TrustedBlockHashOracle oracle;
+
+    function setUp() public {
+        oracle = new TrustedBlockHashOracle();
+    }
+
+    function test_PostBlockHash() public {
+        assertEq(oracle.blockHash(CHAIN_ID, BLOCK_NUMBER), bytes32(0));
+        oracle.postBlockHash(CHAIN_ID, BLOCK_NUMBER, BLOCK_HASH);
+        assertEq(oracle.blockHash(CHAIN_ID, BLOCK_NUMBER), BLOCK_HASH);
+        assertEq(oracle.blockHash(CHAIN_ID + 1, BLOCK_NUMBER), bytes32(0));
+    }
+
+    function test_RevertWhen_NotPoster() public {
+        vm.prank(address(0xBEEF));
+        vm.expectRevert("NOT_POSTER");
+        oracle.postBlockHash(CHAIN_ID, BLOCK_NUMBER, BLOCK_HASH);
+    }
+
+    function test_RevertWhen_BlockHashIsReplaced() public {
+        oracle.postBlockHash(CHAIN_ID, BLOCK_NUMBER, BLOCK_HASH);
+        vm.expectRevert("BLOCK_HASH_ALREADY_POSTED");
+        oracle.postBlockHash(CHAIN_ID, BLOCK_NUMBER, bytes32(uint256(2)));
+    }
+
+    function test_RevertWhen_BlockHashIsZero() public {
+        vm.expectRevert("ZERO_BLOCK_HASH");
+        oracle.postBlockHash(CHAIN_ID, BLOCK_NUMBER, bytes32(0));
+    }
+}
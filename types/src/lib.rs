@@ -0,0 +1,629 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inputs, journals and errors of the zkPOAP guests, shared by the guests and the host.
+
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use alloy_primitives::{eip191_hash_message, keccak256, Address, Signature, B256, U256};
+use alloy_sol_types::{eip712_domain, sol, Eip712Domain, SolStruct, SolValue};
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use light_poseidon::{Poseidon, PoseidonHasher};
+use serde::{Deserialize, Serialize};
+
+sol! {
+    interface POAP {
+        function balanceOf(address owner) external view returns (uint256);
+        function tokenDetailsOfOwnerByIndex(address owner, uint256 index) external view returns (uint256, uint256);
+    }
+}
+
+sol! {
+    /// EIP-712 typed data signed by a POAP holder to join a `POAPGroup`.
+    #[derive(Debug)]
+    struct JoinPOAPGroup {
+        uint256 eventId;
+        uint256 semaphoreCommitment;
+        uint256 chainId;
+        address groupContract;
+        uint256 deadline;
+    }
+
+    /// EIP-712 typed data signed by a POAP holder to join a `POAPSetGroup`.
+    #[derive(Debug)]
+    struct JoinPOAPSetGroup {
+        bytes32 eventSetCommitment;
+        uint256 threshold;
+        uint256 semaphoreCommitment;
+        uint256 chainId;
+        address groupContract;
+        uint256 deadline;
+    }
+}
+
+sol! {
+    /// The proof data committed by the `is_poap_owner` guest.
+    ///
+    /// The fields must match the `ProofData` of `POAPGroup`.
+    #[derive(Debug)]
+    struct ProofData {
+        uint256 eventId;
+        uint256 semaphoreId;
+        bytes32 nullifier;
+        uint256 chainId;
+        address groupContract;
+        uint8 nullifierMode;
+        address poapContract;
+        uint256 poapChainId;
+    }
+
+    /// The proof data committed by the `is_poap_set_member` guest.
+    ///
+    /// The fields must match the `SetProofData` of `POAPSetGroup`.
+    #[derive(Debug)]
+    struct SetProofData {
+        bytes32 eventSetCommitment;
+        uint256 threshold;
+        bool thresholdMet;
+        uint256 semaphoreId;
+        bytes32 nullifier;
+        uint256 chainId;
+        address groupContract;
+        address poapContract;
+        uint256 poapChainId;
+    }
+
+    /// The journal of the `poap_ownership` guest: `owner` held POAP `tokenId` from `eventId` at
+    /// the block committed after it.
+    #[derive(Debug)]
+    struct OwnershipData {
+        address owner;
+        uint256 eventId;
+        uint256 tokenId;
+        address poapContract;
+        uint256 poapChainId;
+    }
+
+    /// The journal of the `owner_signature` guest: `owner` signed the message binding the
+    /// Semaphore identity `semaphoreId` to the group. The deadline is checked by the composing
+    /// guest against the proven block, and is the maximum value for EIP-191 messages.
+    #[derive(Debug)]
+    struct SignatureData {
+        address owner;
+        uint256 eventId;
+        uint256 semaphoreId;
+        uint256 chainId;
+        address groupContract;
+        uint8 nullifierMode;
+        uint256 deadline;
+    }
+
+    /// The block a view call input was derived from, as committed to the journal. The timestamp
+    /// allows checking the freshness of blocks whose hash is not available to `BLOCKHASH`.
+    ///
+    /// The fields must match the `BlockCommitment` of `POAPGroup`.
+    #[derive(Debug)]
+    struct BlockCommitment {
+        bytes32 blockHash;
+        uint256 blockNumber;
+        uint256 blockTimestamp;
+    }
+
+    /// The journal of the `aggregate_holders` guest: `count` distinct holders of a POAP from
+    /// `eventId` of `poapContract` were proven by the `imageId` guest for `groupContract`, at
+    /// the `blocks` of the POAP chain.
+    ///
+    /// The blocks are sorted by number and each listed once, so that verifiers can check them
+    /// like `POAPGroup.validateBlock` does without learning which holder was proven at which block.
+    #[derive(Debug)]
+    struct AggregateData {
+        bytes32 imageId;
+        uint256 eventId;
+        address groupContract;
+        uint256 chainId;
+        uint8 nullifierMode;
+        address poapContract;
+        uint256 poapChainId;
+        BlockCommitment[] blocks;
+        uint256 count;
+    }
+}
+
+#[cfg(feature = "view-call")]
+impl BlockCommitment {
+    /// Extends the commitment of a view call environment with the timestamp of its header.
+    pub fn new(
+        commitment: risc0_ethereum_view_call::BlockCommitment,
+        header: &impl risc0_ethereum_view_call::EvmHeader,
+    ) -> Self {
+        Self {
+            blockHash: commitment.blockHash,
+            blockNumber: commitment.blockNumber,
+            blockTimestamp: U256::from(header.timestamp()),
+        }
+    }
+}
+
+/// The exit code of a guest that rejected its input. The journal then contains a [GuestError]
+/// instead of the proof data, and the receipt can never be verified on-chain, since verifiers
+/// only accept sessions that halted with exit code 0.
+pub const ERROR_EXIT_CODE: u8 = 1;
+
+/// The reasons a guest rejects its input.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum GuestError {
+    /// The POAP contract is deployed on a chain without a chain specification.
+    UnsupportedChain(u64),
+    /// The signature is malformed or does not recover to an address.
+    InvalidSignature,
+    /// The deadline of the typed data passed before the queried block.
+    SignatureExpired,
+    /// The view call input does not match its header, or its ancestors do not form a chain.
+    InvalidState(String),
+    /// A view call reverted or returned invalid data.
+    CallFailed(String),
+    /// The queried POAP is not from the expected event.
+    WrongEvent(U256),
+    /// The historical block is not an ancestor of the current block.
+    InvalidHistoricalBlock,
+    /// The POAP was not held at the historical block.
+    NotHeldSince,
+    /// The event IDs of the set are not sorted in strictly ascending order.
+    UnsortedEventSet,
+    /// The signature of a batch entry recovers to another address than the expected owner.
+    WrongSigner(Address),
+    /// No receipt of the aggregated guest exists for a journal.
+    InvalidAssumption,
+    /// An aggregated journal is not the journal of a zkPOAP proof.
+    InvalidJournal,
+    /// An aggregated proof is for another group than the aggregate.
+    WrongGroup(Address),
+    /// An aggregated proof is for a group on another chain than the aggregate.
+    WrongChain(U256),
+    /// An aggregated proof queried another POAP contract than the aggregate.
+    WrongPoapContract(Address),
+    /// An aggregated proof queried the POAP contract on another chain than the aggregate.
+    WrongPoapChain(U256),
+    /// An aggregated proof has another nullifier mode than the aggregate.
+    WrongNullifierMode(u8),
+    /// The same nullifier appears in several aggregated proofs.
+    DuplicateNullifier(B256),
+}
+
+impl fmt::Display for GuestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuestError::UnsupportedChain(chain_id) => {
+                write!(f, "unsupported POAP chain: {}", chain_id)
+            }
+            GuestError::InvalidSignature => write!(f, "invalid signature"),
+            GuestError::SignatureExpired => write!(f, "signature deadline expired"),
+            GuestError::InvalidState(err) => write!(f, "invalid view call input: {}", err),
+            GuestError::CallFailed(err) => write!(f, "view call failed: {}", err),
+            GuestError::WrongEvent(event_id) => write!(
+                f,
+                "POAP is from event {}, not from the signed event",
+                event_id
+            ),
+            GuestError::InvalidHistoricalBlock => write!(
+                f,
+                "historical block is not an ancestor of the current block"
+            ),
+            GuestError::NotHeldSince => write!(f, "POAP was not held at the historical block"),
+            GuestError::UnsortedEventSet => write!(f, "event IDs are not sorted"),
+            GuestError::WrongSigner(signer) => write!(
+                f,
+                "signature was made by {}, not by the expected owner",
+                signer
+            ),
+            GuestError::InvalidAssumption => write!(f, "aggregated receipt could not be verified"),
+            GuestError::InvalidJournal => write!(f, "aggregated journal could not be decoded"),
+            GuestError::WrongGroup(group) => write!(
+                f,
+                "aggregated proof is for group {}, not for the expected group",
+                group
+            ),
+            GuestError::WrongChain(chain_id) => write!(
+                f,
+                "aggregated proof is for chain {}, not for the expected chain",
+                chain_id
+            ),
+            GuestError::WrongPoapContract(poap_contract) => write!(
+                f,
+                "aggregated proof is for POAP contract {}, not for the expected contract",
+                poap_contract
+            ),
+            GuestError::WrongPoapChain(poap_chain_id) => write!(
+                f,
+                "aggregated proof is for POAP chain {}, not for the expected chain",
+                poap_chain_id
+            ),
+            GuestError::WrongNullifierMode(mode) => write!(
+                f,
+                "aggregated proof has nullifier mode {}, not the expected mode",
+                mode
+            ),
+            GuestError::DuplicateNullifier(nullifier) => {
+                write!(f, "nullifier {} is aggregated more than once", nullifier)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GuestError {}
+
+/// The POAP contract queried by the guest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoapContract {
+    pub address: Address,
+    pub chain_id: u64,
+}
+
+#[cfg(feature = "view-call")]
+impl PoapContract {
+    /// Returns the specification of the chain the POAP contract is deployed on.
+    pub fn chain_spec(
+        &self,
+    ) -> Result<&'static risc0_ethereum_view_call::config::ChainSpec, GuestError> {
+        use risc0_ethereum_view_call::config::{
+            ETH_MAINNET_CHAIN_SPEC, ETH_SEPOLIA_CHAIN_SPEC, GNOSIS_CHAIN_SPEC,
+        };
+
+        match self.chain_id {
+            1 => Ok(&ETH_MAINNET_CHAIN_SPEC),
+            11155111 => Ok(&ETH_SEPOLIA_CHAIN_SPEC),
+            100 => Ok(&GNOSIS_CHAIN_SPEC),
+            chain_id => Err(GuestError::UnsupportedChain(chain_id)),
+        }
+    }
+}
+
+/// The Semaphore identity added to the group.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SemaphoreIdentity {
+    /// The secrets of the identity, from which the guest computes the commitment.
+    ///
+    /// The secrets are enough to send signals as the member, and the guest input may leave the
+    /// machine of the holder, e.g. when it is uploaded to Bonsai, so hosts only pass them on
+    /// request.
+    Secret { trapdoor: U256, nullifier: U256 },
+    /// A precomputed identity commitment.
+    Commitment(U256),
+}
+
+impl SemaphoreIdentity {
+    /// Returns the identity commitment `poseidon(poseidon(nullifier, trapdoor))`.
+    pub fn commitment(&self) -> U256 {
+        match self {
+            SemaphoreIdentity::Secret {
+                trapdoor,
+                nullifier,
+            } => {
+                let secret = poseidon(&[*nullifier, *trapdoor]);
+                poseidon(&[secret])
+            }
+            SemaphoreIdentity::Commitment(commitment) => *commitment,
+        }
+    }
+}
+
+/// Computes the circom-compatible Poseidon hash over the BN254 scalar field.
+pub fn poseidon(inputs: &[U256]) -> U256 {
+    let inputs: Vec<Fr> = inputs
+        .iter()
+        .map(|input| Fr::from_be_bytes_mod_order(&input.to_be_bytes::<32>()))
+        .collect();
+    let hash = Poseidon::<Fr>::new_circom(inputs.len())
+        .and_then(|mut poseidon| poseidon.hash(&inputs))
+        .expect("unsupported number of Poseidon inputs");
+    U256::from_be_slice(&hash.into_bigint().to_bytes_be())
+}
+
+/// What the nullifier of a membership is keyed on.
+///
+/// The variants must match the `NullifierMode` enum of `POAPGroup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum NullifierMode {
+    /// One membership per owner address.
+    Address,
+    /// One membership per POAP token, regardless of transfers.
+    Token,
+}
+
+impl TryFrom<u8> for NullifierMode {
+    type Error = GuestError;
+
+    fn try_from(mode: u8) -> Result<Self, GuestError> {
+        match mode {
+            0 => Ok(NullifierMode::Address),
+            1 => Ok(NullifierMode::Token),
+            _ => Err(GuestError::InvalidJournal),
+        }
+    }
+}
+
+/// The format of the message signed by the POAP holder. Any other format is rejected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MessageFormat {
+    /// A `personal_sign` message, hashed as specified by EIP-191.
+    Eip191,
+    /// An `eth_signTypedData_v4` message, hashed as specified by EIP-712.
+    Eip712 { deadline: U256 },
+}
+
+impl MessageFormat {
+    /// Returns an error if the typed data deadline passed before the given block timestamp.
+    pub fn check_deadline(&self, timestamp: u64) -> Result<(), GuestError> {
+        match self {
+            MessageFormat::Eip712 { deadline } if *deadline < U256::from(timestamp) => {
+                Err(GuestError::SignatureExpired)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the deadline of the signature, which is unlimited for EIP-191 messages.
+    pub fn deadline(&self) -> U256 {
+        match self {
+            MessageFormat::Eip191 => U256::MAX,
+            MessageFormat::Eip712 { deadline } => *deadline,
+        }
+    }
+}
+
+/// The public inputs the signed message is bound to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupBinding {
+    pub event_id: U256,
+    pub group_contract: Address,
+    pub chain_id: u64,
+    pub semaphore_identity: SemaphoreIdentity,
+    pub nullifier_mode: NullifierMode,
+}
+
+impl GroupBinding {
+    /// Returns the Semaphore group ID of the `POAPGroup`, as computed by its constructor.
+    pub fn group_id(&self) -> U256 {
+        let hash = keccak256((self.group_contract, self.event_id).abi_encode());
+        U256::from_be_bytes(hash.0)
+    }
+
+    /// Returns the nullifier of the membership, i.e. `keccak256(abi.encode(owner, groupId))` or
+    /// `keccak256(abi.encode(tokenId, groupId))` depending on the nullifier mode.
+    ///
+    /// It only depends on the owner or the token and the group, so one address or token joins a
+    /// group at most once, no matter how many signatures exist.
+    pub fn nullifier(&self, owner: Address, token_id: U256) -> B256 {
+        match self.nullifier_mode {
+            NullifierMode::Address => keccak256((owner, self.group_id()).abi_encode()),
+            NullifierMode::Token => keccak256((token_id, self.group_id()).abi_encode()),
+        }
+    }
+
+    /// Returns the text of the EIP-191 message binding the holder to the group.
+    pub fn eip191_message(&self) -> String {
+        format!(
+            "Join POAPGroup {} on chain {} as a holder of a POAP from event {} with Semaphore identity commitment {}",
+            self.group_contract,
+            self.chain_id,
+            self.event_id,
+            self.semaphore_identity.commitment()
+        )
+    }
+
+    /// Returns the EIP-712 typed data binding the holder to the group.
+    pub fn eip712_message(&self, deadline: U256) -> JoinPOAPGroup {
+        JoinPOAPGroup {
+            eventId: self.event_id,
+            semaphoreCommitment: self.semaphore_identity.commitment(),
+            chainId: U256::from(self.chain_id),
+            groupContract: self.group_contract,
+            deadline,
+        }
+    }
+
+    /// Rebuilds the expected message in the given format and returns the hash signed by the wallet.
+    pub fn signing_hash(&self, format: &MessageFormat) -> B256 {
+        match format {
+            MessageFormat::Eip191 => eip191_hash_message(self.eip191_message()),
+            MessageFormat::Eip712 { deadline } => self
+                .eip712_message(*deadline)
+                .eip712_signing_hash(&eip712_domain(self.chain_id, self.group_contract)),
+        }
+    }
+}
+
+/// The public inputs of the `is_poap_set_member` guest the signed message is bound to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetBinding {
+    /// The event IDs of the set, sorted in strictly ascending order.
+    pub event_ids: Vec<U256>,
+    /// The number of distinct events of the set the holder must have a POAP from.
+    pub threshold: u64,
+    pub group_contract: Address,
+    pub chain_id: u64,
+    pub semaphore_identity: SemaphoreIdentity,
+}
+
+impl SetBinding {
+    /// Returns an error unless the event IDs are sorted in strictly ascending order, so that
+    /// every set has exactly one commitment.
+    pub fn check_sorted(&self) -> Result<(), GuestError> {
+        if self.event_ids.windows(2).all(|ids| ids[0] < ids[1]) {
+            Ok(())
+        } else {
+            Err(GuestError::UnsortedEventSet)
+        }
+    }
+
+    /// Returns the commitment to the event set, i.e. `keccak256(abi.encode(eventIds))`, as
+    /// stored by `POAPSetGroup`.
+    pub fn event_set_commitment(&self) -> B256 {
+        keccak256(self.event_ids.abi_encode())
+    }
+
+    /// Returns the Semaphore group ID of the `POAPSetGroup`, as computed by its constructor.
+    pub fn group_id(&self) -> U256 {
+        let hash = keccak256((self.group_contract, self.event_set_commitment()).abi_encode());
+        U256::from_be_bytes(hash.0)
+    }
+
+    /// Returns the nullifier of the membership, i.e. `keccak256(abi.encode(owner, groupId))`.
+    pub fn nullifier(&self, owner: Address) -> B256 {
+        keccak256((owner, self.group_id()).abi_encode())
+    }
+
+    /// Returns the text of the EIP-191 message binding the holder to the group.
+    pub fn eip191_message(&self) -> String {
+        format!(
+            "Join POAPSetGroup {} on chain {} as a holder of POAPs from at least {} events of set {} with Semaphore identity commitment {}",
+            self.group_contract,
+            self.chain_id,
+            self.threshold,
+            self.event_set_commitment(),
+            self.semaphore_identity.commitment()
+        )
+    }
+
+    /// Returns the EIP-712 typed data binding the holder to the group.
+    pub fn eip712_message(&self, deadline: U256) -> JoinPOAPSetGroup {
+        JoinPOAPSetGroup {
+            eventSetCommitment: self.event_set_commitment(),
+            threshold: U256::from(self.threshold),
+            semaphoreCommitment: self.semaphore_identity.commitment(),
+            chainId: U256::from(self.chain_id),
+            groupContract: self.group_contract,
+            deadline,
+        }
+    }
+
+    /// Rebuilds the expected message in the given format and returns the hash signed by the wallet.
+    pub fn signing_hash(&self, format: &MessageFormat) -> B256 {
+        match format {
+            MessageFormat::Eip191 => eip191_hash_message(self.eip191_message()),
+            MessageFormat::Eip712 { deadline } => self
+                .eip712_message(*deadline)
+                .eip712_signing_hash(&eip712_domain(self.chain_id, self.group_contract)),
+        }
+    }
+}
+
+/// A POAP holder joining the group as part of the batch of the `is_poap_owner_batch` guest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEntry {
+    pub binding: GroupBinding,
+    pub format: MessageFormat,
+    pub signature: Vec<u8>,
+    /// The expected signer, so that a mismatch is reported for the right entry.
+    pub owner: Address,
+    pub poap_index: U256,
+}
+
+/// Returns the EIP-712 domain of a group contract, the same for `POAPGroup` and `POAPSetGroup`.
+pub fn eip712_domain(chain_id: u64, group_contract: Address) -> Eip712Domain {
+    eip712_domain! {
+        name: "zkPOAP",
+        version: "1",
+        chain_id: chain_id,
+        verifying_contract: group_contract,
+    }
+}
+
+/// Recovers the POAP owner from the 65-byte `r || s || v` signature over the expected message,
+/// so that a signature over any other message recovers a different address.
+pub fn recover_owner(signature: &[u8], signing_hash: &B256) -> Result<Address, GuestError> {
+    Signature::try_from(signature)
+        .and_then(|signature| signature.recover_address_from_prehash(signing_hash))
+        .map_err(|_| GuestError::InvalidSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::uint;
+
+    #[test]
+    fn poseidon_circom_vector() {
+        assert_eq!(
+            poseidon(&[U256::from(1), U256::from(2)]),
+            uint!(0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a_U256)
+        );
+    }
+
+    #[test]
+    fn secret_commitment() {
+        // The commitment of `@semaphore-protocol/identity` v3 with trapdoor 1 and nullifier 2,
+        // i.e. `poseidon([poseidon([2, 1])])` with the circom parameters.
+        let expected =
+            uint!(0x19b6135b8ee3d4ae9ad3adc6658cc80ab5783e9b0eee92932ef70dde8146db40_U256);
+        let identity = SemaphoreIdentity::Secret {
+            trapdoor: U256::from(1),
+            nullifier: U256::from(2),
+        };
+        assert_eq!(identity.commitment(), expected);
+
+        let binding = GroupBinding {
+            event_id: U256::from(1),
+            group_contract: Address::ZERO,
+            chain_id: 100,
+            semaphore_identity: identity,
+            nullifier_mode: NullifierMode::Address,
+        };
+        assert!(binding.eip191_message().ends_with(&expected.to_string()));
+    }
+
+    #[test]
+    fn token_nullifier_ignores_owner() {
+        let binding = GroupBinding {
+            event_id: U256::from(1),
+            group_contract: Address::ZERO,
+            chain_id: 100,
+            semaphore_identity: SemaphoreIdentity::Commitment(U256::ZERO),
+            nullifier_mode: NullifierMode::Token,
+        };
+        let token_id = U256::from(42);
+
+        assert_eq!(
+            binding.nullifier(Address::ZERO, token_id),
+            binding.nullifier(Address::repeat_byte(1), token_id)
+        );
+        assert_ne!(
+            binding.nullifier(Address::ZERO, token_id),
+            binding.nullifier(Address::ZERO, token_id + U256::from(1))
+        );
+    }
+
+    #[test]
+    fn unsorted_event_set() {
+        let binding = SetBinding {
+            event_ids: vec![U256::from(2), U256::from(1)],
+            threshold: 1,
+            group_contract: Address::ZERO,
+            chain_id: 100,
+            semaphore_identity: SemaphoreIdentity::Commitment(U256::ZERO),
+        };
+        assert!(matches!(
+            binding.check_sorted(),
+            Err(GuestError::UnsortedEventSet)
+        ));
+    }
+}